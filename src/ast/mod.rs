@@ -0,0 +1,275 @@
+//! Syntax tree produced by [`crate::parser::parse`].
+//!
+//! Every statement and expression keeps the [`Span`] of the source it was parsed
+//! from, so tools can point back at the program. The `Display` impls print the
+//! tree the way the `ast` command shows it, with expressions as s-expressions.
+use crate::scanner::Span;
+use crate::value::Value;
+use std::fmt;
+
+/// A list of statements, the body of a program or of a block.
+pub type Block = Vec<Stmt>;
+
+/// A statement and the span from its first to its last token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+// FOR is much bigger than the other statements, but trees are built once and only read after
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind {
+    /// `LET <name> BE <value>`, declares a variable.
+    Let { name: Ident, value: Expr },
+    /// `<name> BE <value>`, updates a variable declared before.
+    Assign { name: Ident, value: Expr },
+    /// `PRINT <item> [, <item>] [; <item>] ...`.
+    Print(Vec<PrintItem>),
+    /// `IF <condition> ... [ELIF <condition> ...] [ELSE ...] END`, the IF
+    /// itself is the first branch.
+    If { branches: Vec<Branch>, otherwise: Option<Block> },
+    /// `FOR <variable> IN <start> TO <end> [STEP <step>] ... END`.
+    For { variable: Ident, start: Expr, end: Expr, step: Option<Expr>, body: Block },
+    /// `WHILE <condition> ... END`.
+    While { condition: Expr, body: Block },
+    /// `FUNCTION <name>(<param>, ...) ... END`. `locals` is filled in by
+    /// [`crate::resolver`], the parameters are its first entries.
+    Function { name: Ident, params: Vec<Ident>, body: Block, locals: Vec<String> },
+    /// `GLOBAL <name> [, <name>] ...`, the names refer to global variables for the
+    /// rest of the block, even when they are given a value with `LET`.
+    Global(Vec<Ident>),
+    /// `RETURN [<value>]`.
+    Return(Option<Expr>),
+    Break,
+    Continue,
+    /// An expression on a line of its own, a call whose result is dropped.
+    Expr(Expr),
+}
+
+/// A condition and the statements run when it holds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Branch {
+    pub condition: Expr,
+    pub body: Block,
+}
+
+/// One piece of a PRINT list. A list that ends with a separator doesn't end the line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrintItem {
+    Expr(Expr),
+    /// `,`, moves to the next print zone.
+    Zone,
+    /// `;`, prints the next item right after the previous one.
+    Join,
+}
+
+/// A variable, parameter or function name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+    /// Where the name is stored at runtime, set by [`crate::resolver`].
+    pub slot: Slot,
+}
+
+impl Ident {
+    /// A name that hasn't been resolved yet.
+    pub fn new(name: &str, span: Span) -> Ident {
+        Ident { name: name.to_string(), span, slot: Slot::Unresolved }
+    }
+}
+
+/// Where a name lives once [`crate::resolver`] has run.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Slot {
+    #[default]
+    Unresolved,
+    /// A global variable, or the index in the function table for a function name.
+    Global(usize),
+    /// A local slot of the function the name is used in, or of the top level code
+    /// for the variables of its blocks.
+    Local(usize),
+}
+
+
+/// An expression and the span of every token it was parsed from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Literal(Value),
+    Variable(Ident),
+    /// `operator` is the span of the operator token.
+    Unary { op: UnaryOp, operator: Span, operand: Box<Expr> },
+    /// `operator` is the span of the operator token.
+    Binary { op: BinaryOp, operator: Span, left: Box<Expr>, right: Box<Expr> },
+    Call { name: Ident, args: Vec<Expr> },
+    /// An expression between parenthesis.
+    Grouping(Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    /// `NOT`
+    Not,
+    /// `-`
+    Negate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    /// `\`, the quotient of two integers rounded toward zero
+    IntDivide,
+    Modulo,
+    Power,
+    /// `&`
+    Concat,
+    /// `EQUALS`
+    Equal,
+    /// `NOTEQUALS`
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+}
+
+impl UnaryOp {
+    /// The operator as it is written in a program.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            UnaryOp::Not => "NOT",
+            UnaryOp::Negate => "-",
+        }
+    }
+}
+
+impl BinaryOp {
+    /// The operator as it is written in a program.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::IntDivide => "\\",
+            BinaryOp::Modulo => "%",
+            BinaryOp::Power => "^",
+            BinaryOp::Concat => "&",
+            BinaryOp::Equal => "EQUALS",
+            BinaryOp::NotEqual => "NOTEQUALS",
+            BinaryOp::Less => "<",
+            BinaryOp::LessEqual => "<=",
+            BinaryOp::Greater => ">",
+            BinaryOp::GreaterEqual => ">=",
+            BinaryOp::And => "AND",
+            BinaryOp::Or => "OR",
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            ExprKind::Literal(Value::String(text)) => write!(f, "\"{}\"", text),
+            ExprKind::Literal(value) => write!(f, "{}", value),
+            ExprKind::Variable(name) => write!(f, "{}", name.name),
+            ExprKind::Unary { op, operand, .. } => write!(f, "({} {})", op.symbol(), operand),
+            ExprKind::Binary { op, left, right, .. } => write!(f, "({} {} {})", op.symbol(), left, right),
+            ExprKind::Call { name, args } => {
+                write!(f, "({}", name.name)?;
+                for arg in args {
+                    write!(f, " {}", arg)?;
+                }
+                write!(f, ")")
+            },
+            ExprKind::Grouping(inner) => write!(f, "(group {})", inner),
+        }
+    }
+}
+
+fn write_block(f: &mut fmt::Formatter, block: &[Stmt], indent: usize) -> fmt::Result {
+    for stmt in block {
+        write_stmt(f, stmt, indent)?;
+    }
+    Ok(())
+}
+
+fn write_stmt(f: &mut fmt::Formatter, stmt: &Stmt, indent: usize) -> fmt::Result {
+    let pad = "  ".repeat(indent);
+    match &stmt.kind {
+        StmtKind::Let { name, value } => writeln!(f, "{}LET {} {}", pad, name.name, value),
+        StmtKind::Assign { name, value } => writeln!(f, "{}ASSIGN {} {}", pad, name.name, value),
+        StmtKind::Print(items) => {
+            write!(f, "{}PRINT", pad)?;
+            for item in items {
+                match item {
+                    PrintItem::Expr(expr) => write!(f, " {}", expr)?,
+                    PrintItem::Zone => write!(f, " ,")?,
+                    PrintItem::Join => write!(f, " ;")?,
+                }
+            }
+            writeln!(f)
+        },
+        StmtKind::If { branches, otherwise } => {
+            for (n, branch) in branches.iter().enumerate() {
+                let keyword = if n == 0 { "IF" } else { "ELIF" };
+                writeln!(f, "{}{} {}", pad, keyword, branch.condition)?;
+                write_block(f, &branch.body, indent + 1)?;
+            }
+            if let Some(body) = otherwise {
+                writeln!(f, "{}ELSE", pad)?;
+                write_block(f, body, indent + 1)?;
+            }
+            writeln!(f, "{}END", pad)
+        },
+        StmtKind::For { variable, start, end, step, body } => {
+            write!(f, "{}FOR {} IN {} TO {}", pad, variable.name, start, end)?;
+            if let Some(step) = step {
+                write!(f, " STEP {}", step)?;
+            }
+            writeln!(f)?;
+            write_block(f, body, indent + 1)?;
+            writeln!(f, "{}END", pad)
+        },
+        StmtKind::While { condition, body } => {
+            writeln!(f, "{}WHILE {}", pad, condition)?;
+            write_block(f, body, indent + 1)?;
+            writeln!(f, "{}END", pad)
+        },
+        StmtKind::Function { name, params, body, .. } => {
+            let params: Vec<&str> = params.iter().map(|param| param.name.as_str()).collect();
+            writeln!(f, "{}FUNCTION {}({})", pad, name.name, params.join(", "))?;
+            write_block(f, body, indent + 1)?;
+            writeln!(f, "{}END", pad)
+        },
+        StmtKind::Global(names) => {
+            let names: Vec<&str> = names.iter().map(|name| name.name.as_str()).collect();
+            writeln!(f, "{}GLOBAL {}", pad, names.join(", "))
+        },
+        StmtKind::Return(Some(value)) => writeln!(f, "{}RETURN {}", pad, value),
+        StmtKind::Return(None) => writeln!(f, "{}RETURN", pad),
+        StmtKind::Break => writeln!(f, "{}BREAK", pad),
+        StmtKind::Continue => writeln!(f, "{}CONTINUE", pad),
+        StmtKind::Expr(expr) => writeln!(f, "{}{}", pad, expr),
+    }
+}
+
+// a statement and every block nested in it, indented two spaces per level
+impl fmt::Display for Stmt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_stmt(f, self, 0)
+    }
+}
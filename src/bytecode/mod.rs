@@ -0,0 +1,246 @@
+//! Instructions produced by [`crate::compiler::compile`] and run by [`crate::vm::Vm`].
+//!
+//! A [`Program`] is a flat list of instructions with the tables they index into.
+//! The top level code starts at instruction 0 and ends with [`Op::Halt`], the body
+//! of every function follows it. Values live on a stack: operators pop their
+//! operands and push their result.
+use crate::scanner::Span;
+use crate::value::Value;
+use std::fmt::Write;
+
+/// One instruction. Operands index the tables of the [`Program`], a local slot of
+/// the current call or, for jumps, the code itself.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    /// Pushes `constants[n]`.
+    Constant(u16),
+    /// Pushes the global variable `names[n]`.
+    Load(u16),
+    /// Pops a value into the global variable `names[n]`, which has to exist.
+    Store(u16),
+    /// Pops a value into the global variable `names[n]`, creating it (`LET`).
+    Define(u16),
+    /// Pushes local slot `n` of the current call, or of the top level code outside
+    /// of calls. A slot that hasn't been set yet is an undefined symbol.
+    LoadLocal(u16),
+    /// Pops a value into local slot `n`, which has to be set already.
+    StoreLocal(u16),
+    /// Pops a value into local slot `n` (`LET` inside a block).
+    DefineLocal(u16),
+    Pop,
+    Not,
+    Negate,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    IntDivide,
+    Modulo,
+    Power,
+    Concat,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+    Jump(u32),
+    /// Pops a boolean and jumps when it is `FALSE`.
+    JumpIfFalse(u32),
+    /// Checks that the value on top of the stack can be used as that part of a FOR loop.
+    ForNumber(LoopPart),
+    /// With the counter, bound and step of a FOR loop on the stack, jumps when the
+    /// counter went past the bound and pushes a copy of the counter otherwise.
+    ForTest(u32),
+    /// Adds the step of a FOR loop to its counter, or jumps when the counter can't
+    /// take another step without overflowing past an integer bound.
+    ForStep(u32),
+    /// Makes `functions[n]` callable by its name.
+    DefineFunction(u16),
+    /// Pops `argc` arguments and calls the function named `names[name]`. The function
+    /// has to return a value, which is pushed.
+    Call { name: u16, argc: u16 },
+    /// Like [`Op::Call`], but whatever the function returns is dropped.
+    CallDiscard { name: u16, argc: u16 },
+    /// Pops the return value and leaves the current call.
+    Return,
+    /// Leaves the current call without a value.
+    ReturnNone,
+    /// Pops a value and adds it to the pending PRINT output.
+    Print,
+    /// Moves the pending PRINT output to the next print zone.
+    PrintZone,
+    /// Writes the pending PRINT output, ending the line when `newline` is set.
+    PrintEnd { newline: bool },
+    Halt,
+}
+
+/// The part of a FOR loop header a number is checked for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoopPart {
+    Start,
+    Bound,
+    Step,
+}
+
+impl LoopPart {
+    /// The name used in type errors.
+    pub fn name(self) -> &'static str {
+        match self {
+            LoopPart::Start => "START",
+            LoopPart::Bound => "BOUND",
+            LoopPart::Step => "STEP",
+        }
+    }
+}
+
+/// Where an instruction came from. `at` is where its errors point and `value` is the
+/// span of the expression whose value it pushes, they differ for operators and calls.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Origin {
+    pub at: Span,
+    pub value: Span,
+}
+
+/// A FUNCTION definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    /// Index of the function's name in [`Program::names`].
+    pub name: u16,
+    /// How many arguments it takes, they fill the first local slots.
+    pub arity: u16,
+    /// The name of every local slot as an index into [`Program::names`], so a slot
+    /// can fall back to the global with the same name.
+    pub locals: Vec<u16>,
+    /// Index of the function's first instruction.
+    pub start: u32,
+}
+
+/// A compiled program.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Program {
+    pub constants: Vec<Value>,
+    /// Names of global variables and functions, the index of a name is its global slot.
+    pub names: Vec<String>,
+    pub functions: Vec<Function>,
+    /// The name of every local slot of the top level code, for the variables
+    /// declared inside its blocks.
+    pub locals: Vec<u16>,
+    pub code: Vec<Op>,
+    /// One entry for every instruction in `code`.
+    pub origins: Vec<Origin>,
+}
+
+impl Op {
+    /// The name of the instruction in a listing.
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Op::Constant(_) => "CONSTANT",
+            Op::Load(_) => "LOAD",
+            Op::Store(_) => "STORE",
+            Op::Define(_) => "DEFINE",
+            Op::LoadLocal(_) => "LOAD_LOCAL",
+            Op::StoreLocal(_) => "STORE_LOCAL",
+            Op::DefineLocal(_) => "DEFINE_LOCAL",
+            Op::Pop => "POP",
+            Op::Not => "NOT",
+            Op::Negate => "NEGATE",
+            Op::Add => "ADD",
+            Op::Subtract => "SUBTRACT",
+            Op::Multiply => "MULTIPLY",
+            Op::Divide => "DIVIDE",
+            Op::IntDivide => "INT_DIVIDE",
+            Op::Modulo => "MODULO",
+            Op::Power => "POWER",
+            Op::Concat => "CONCAT",
+            Op::Equal => "EQUAL",
+            Op::NotEqual => "NOT_EQUAL",
+            Op::Less => "LESS",
+            Op::LessEqual => "LESS_EQUAL",
+            Op::Greater => "GREATER",
+            Op::GreaterEqual => "GREATER_EQUAL",
+            Op::And => "AND",
+            Op::Or => "OR",
+            Op::Jump(_) => "JUMP",
+            Op::JumpIfFalse(_) => "JUMP_IF_FALSE",
+            Op::ForNumber(_) => "FOR_NUMBER",
+            Op::ForTest(_) => "FOR_TEST",
+            Op::ForStep(_) => "FOR_STEP",
+            Op::DefineFunction(_) => "DEFINE_FUNCTION",
+            Op::Call { .. } => "CALL",
+            Op::CallDiscard { .. } => "CALL_DISCARD",
+            Op::Return => "RETURN",
+            Op::ReturnNone => "RETURN_NONE",
+            Op::Print => "PRINT",
+            Op::PrintZone => "PRINT_ZONE",
+            Op::PrintEnd { .. } => "PRINT_END",
+            Op::Halt => "HALT",
+        }
+    }
+}
+
+impl Program {
+    // the signature shown above a function's code, FACT(N)
+    fn signature(&self, function: &Function) -> String {
+        let params: Vec<&str> = function.locals
+            .iter()
+            .take(function.arity as usize)
+            .map(|&name| self.names[name as usize].as_str())
+            .collect();
+        std::format!("{}({})", self.names[function.name as usize], params.join(", "))
+    }
+
+    // the operand of an instruction, with the constant or name it points at
+    fn operand(&self, op: Op, locals: &[u16]) -> String {
+        let local = |slot: u16| match locals.get(slot as usize) {
+            Some(&name) => std::format!("{} {}", slot, self.names[name as usize]),
+            None => slot.to_string(),
+        };
+        match op {
+            Op::Constant(n) => match &self.constants[n as usize] {
+                Value::String(text) => std::format!("{} \"{}\"", n, text.escape_default()),
+                // 1.0 rather than 1, so a float can't be taken for an integer
+                Value::Float(float) => std::format!("{} {:?}", n, float),
+                value => std::format!("{} {}", n, value),
+            },
+            Op::Load(n) | Op::Store(n) | Op::Define(n) => std::format!("{} {}", n, self.names[n as usize]),
+            Op::LoadLocal(slot) | Op::StoreLocal(slot) | Op::DefineLocal(slot) => local(slot),
+            Op::Jump(target) | Op::JumpIfFalse(target) | Op::ForTest(target) | Op::ForStep(target) => std::format!("-> {:04}", target),
+            Op::ForNumber(part) => part.name().to_string(),
+            Op::DefineFunction(n) => std::format!("{} {}", n, self.signature(&self.functions[n as usize])),
+            Op::Call { name, argc } | Op::CallDiscard { name, argc } => {
+                std::format!("{} ({} ARGUMENTS)", self.names[name as usize], argc)
+            },
+            Op::PrintEnd { newline: true } => "NEWLINE".to_string(),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Lists every instruction with its index, the source line it came from and its
+/// operand, under a header for the top level code and for each function.
+pub fn disassemble(program: &Program) -> String {
+    let mut listing = String::from("== <main> ==\n");
+    let mut locals = &program.locals;
+    let mut line = None;
+    for (idx, &op) in program.code.iter().enumerate() {
+        if let Some(started) = program.functions.iter().find(|function| function.start as usize == idx) {
+            let _ = writeln!(listing, "\n== {} ==", program.signature(started));
+            locals = &started.locals;
+            line = None;
+        }
+        // a line is only shown when it changes, programs loaded without a line table have none
+        let at = program.origins.get(idx).map_or(0, |origin| origin.at.line);
+        let shown = match at {
+            _ if line == Some(at) => "|".to_string(),
+            0 => "-".to_string(),
+            at => at.to_string(),
+        };
+        line = Some(at);
+        let entry = std::format!("{:04} {:>4} {:<16}{}", idx, shown, op.mnemonic(), program.operand(op, locals));
+        let _ = writeln!(listing, "{}", entry.trim_end());
+    }
+    listing
+}
@@ -0,0 +1,350 @@
+//! Compiles the syntax tree from [`crate::parser::parse`] to the bytecode in [`crate::bytecode`].
+//!
+//! The program is resolved with [`crate::resolver`] first. Global variables and
+//! functions are addressed by the index of their name, locals by the slot the
+//! resolver gave them, in the frame of their function or of the top level code.
+use crate::ast::{BinaryOp, Expr, ExprKind, Ident, PrintItem, Slot, Stmt, StmtKind, UnaryOp};
+use crate::bytecode::{Function, LoopPart, Op, Origin, Program};
+use crate::error::{CompileError, Error};
+use crate::resolver;
+use crate::scanner::Span;
+use crate::value::Value;
+use std::collections::HashMap;
+
+/// Compiles a whole program.
+pub fn compile(program: &[Stmt]) -> Result<Program, Error> {
+    let mut program = program.to_vec();
+    let locals = resolver::resolve(&mut program)?;
+    let mut compiler = Compiler::default();
+    compiler.program.locals = compiler.names(&locals, Span::default())?;
+    compiler.block(&program)?;
+    compiler.emit(Op::Halt, Span::default());
+
+    // bodies are compiled after the code that defines them, nested functions last
+    let mut next = 0;
+    while next < compiler.pending.len() {
+        let (index, body) = compiler.pending[next];
+        next += 1;
+        compiler.program.functions[index].start = compiler.program.code.len() as u32;
+        compiler.block(body)?;
+        compiler.emit(Op::ReturnNone, Span::default());
+    }
+    Ok(compiler.program)
+}
+
+fn binary_op(op: BinaryOp) -> Op {
+    match op {
+        BinaryOp::Add => Op::Add,
+        BinaryOp::Subtract => Op::Subtract,
+        BinaryOp::Multiply => Op::Multiply,
+        BinaryOp::Divide => Op::Divide,
+        BinaryOp::IntDivide => Op::IntDivide,
+        BinaryOp::Modulo => Op::Modulo,
+        BinaryOp::Power => Op::Power,
+        BinaryOp::Concat => Op::Concat,
+        BinaryOp::Equal => Op::Equal,
+        BinaryOp::NotEqual => Op::NotEqual,
+        BinaryOp::Less => Op::Less,
+        BinaryOp::LessEqual => Op::LessEqual,
+        BinaryOp::Greater => Op::Greater,
+        BinaryOp::GreaterEqual => Op::GreaterEqual,
+        BinaryOp::And => Op::And,
+        BinaryOp::Or => Op::Or,
+    }
+}
+
+// jumps waiting for the end of a loop (BREAK) or the start of its next pass (CONTINUE)
+#[derive(Default)]
+struct Loop {
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+#[derive(Default)]
+struct Compiler<'a> {
+    program: Program,
+    // where each name and constant already is, so every one is stored once
+    name_slots: HashMap<String, u16>,
+    // functions whose body still has to be compiled
+    pending: Vec<(usize, &'a [Stmt])>,
+    loops: Vec<Loop>,
+}
+
+impl<'a> Compiler<'a> {
+    fn emit_with(&mut self, op: Op, origin: Origin) -> usize {
+        self.program.code.push(op);
+        self.program.origins.push(origin);
+        self.program.code.len() - 1
+    }
+
+    fn emit(&mut self, op: Op, span: Span) -> usize {
+        self.emit_with(op, Origin { at: span, value: span })
+    }
+
+    // where the next instruction will go
+    fn here(&self) -> u32 {
+        self.program.code.len() as u32
+    }
+
+    // points the jump at idx to target
+    fn patch(&mut self, idx: usize, target: u32) {
+        self.program.code[idx] = match self.program.code[idx] {
+            Op::Jump(_) => Op::Jump(target),
+            Op::JumpIfFalse(_) => Op::JumpIfFalse(target),
+            Op::ForTest(_) => Op::ForTest(target),
+            Op::ForStep(_) => Op::ForStep(target),
+            op => op,
+        };
+    }
+
+    fn index(count: usize, what: &str, span: Span) -> Result<u16, Error> {
+        match u16::try_from(count) {
+            Ok(index) => Ok(index),
+            Err(_) => Err(CompileError::TooMany { what: what.to_string(), span }.into()),
+        }
+    }
+
+    fn name(&mut self, name: &str, span: Span) -> Result<u16, Error> {
+        if let Some(&slot) = self.name_slots.get(name) {
+            return Ok(slot);
+        }
+        let slot = Self::index(self.program.names.len(), "NAMES", span)?;
+        self.program.names.push(name.to_string());
+        self.name_slots.insert(name.to_string(), slot);
+        Ok(slot)
+    }
+
+    // the names of the local slots of a function or of the top level code
+    fn names(&mut self, locals: &[String], span: Span) -> Result<Vec<u16>, Error> {
+        Self::index(locals.len(), "LOCAL VARIABLES", span)?;
+        locals.iter().map(|local| self.name(local, span)).collect()
+    }
+
+    fn constant(&mut self, value: &Value, span: Span) -> Result<u16, Error> {
+        if let Some(index) = self.program.constants.iter().position(|constant| constant == value) {
+            return Ok(index as u16);
+        }
+        let index = Self::index(self.program.constants.len(), "CONSTANTS", span)?;
+        self.program.constants.push(value.clone());
+        Ok(index)
+    }
+
+    // a resolved local slot as an operand
+    fn local(slot: usize, span: Span) -> Result<u16, Error> {
+        Self::index(slot, "LOCAL VARIABLES", span)
+    }
+
+    // pops a value into a variable, define is set for LET and FOR
+    fn store(&mut self, name: &Ident, define: bool) -> Result<(), Error> {
+        let op = match (name.slot, define) {
+            (Slot::Local(slot), true) => Op::DefineLocal(Self::local(slot, name.span)?),
+            (Slot::Local(slot), false) => Op::StoreLocal(Self::local(slot, name.span)?),
+            (_, true) => Op::Define(self.name(&name.name, name.span)?),
+            (_, false) => Op::Store(self.name(&name.name, name.span)?),
+        };
+        self.emit(op, name.span);
+        Ok(())
+    }
+
+    fn block(&mut self, block: &'a [Stmt]) -> Result<(), Error> {
+        for stmt in block {
+            self.statement(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &'a Stmt) -> Result<(), Error> {
+        match &stmt.kind {
+            StmtKind::Let { name, value } => {
+                self.expression(value)?;
+                self.store(name, true)?;
+            },
+            StmtKind::Assign { name, value } => {
+                self.expression(value)?;
+                self.store(name, false)?;
+            },
+            StmtKind::Print(items) => {
+                for item in items {
+                    match item {
+                        PrintItem::Expr(expr) => {
+                            self.expression(expr)?;
+                            self.emit(Op::Print, stmt.span);
+                        },
+                        PrintItem::Zone => {
+                            self.emit(Op::PrintZone, stmt.span);
+                        },
+                        PrintItem::Join => {},
+                    }
+                }
+                let newline = matches!(items.last(), None | Some(PrintItem::Expr(_)));
+                self.emit(Op::PrintEnd { newline }, stmt.span);
+            },
+            // every branch that runs jumps over the ones after it
+            StmtKind::If { branches, otherwise } => {
+                let mut ends = Vec::new();
+                for (n, branch) in branches.iter().enumerate() {
+                    self.expression(&branch.condition)?;
+                    let skip = self.emit(Op::JumpIfFalse(0), branch.condition.span);
+                    self.block(&branch.body)?;
+                    if n + 1 < branches.len() || otherwise.is_some() {
+                        ends.push(self.emit(Op::Jump(0), stmt.span));
+                    }
+                    let next = self.here();
+                    self.patch(skip, next);
+                }
+                if let Some(body) = otherwise {
+                    self.block(body)?;
+                }
+                let end = self.here();
+                for jump in ends {
+                    self.patch(jump, end);
+                }
+            },
+            // the counter, bound and step stay on the stack while the loop runs
+            StmtKind::For { variable, start, end, step, body } => {
+                self.expression(start)?;
+                self.emit(Op::ForNumber(LoopPart::Start), start.span);
+                self.expression(end)?;
+                self.emit(Op::ForNumber(LoopPart::Bound), end.span);
+                match step {
+                    Some(step) => {
+                        self.expression(step)?;
+                        self.emit(Op::ForNumber(LoopPart::Step), step.span);
+                    },
+                    None => {
+                        let one = self.constant(&Value::Integer(1), stmt.span)?;
+                        self.emit(Op::Constant(one), stmt.span);
+                    },
+                }
+                let top = self.here();
+                let test = self.emit(Op::ForTest(0), stmt.span);
+                self.store(variable, true)?;
+                self.loops.push(Loop::default());
+                self.block(body)?;
+                let next = self.here();
+                let step = self.emit(Op::ForStep(0), stmt.span);
+                self.emit(Op::Jump(top), stmt.span);
+                let done = self.here();
+                self.patch(test, done);
+                self.patch(step, done);
+                self.end_loop(next, done);
+                for _ in 0..3 {
+                    self.emit(Op::Pop, stmt.span);
+                }
+            },
+            StmtKind::While { condition, body } => {
+                let top = self.here();
+                self.expression(condition)?;
+                let exit = self.emit(Op::JumpIfFalse(0), condition.span);
+                self.loops.push(Loop::default());
+                self.block(body)?;
+                self.emit(Op::Jump(top), stmt.span);
+                let done = self.here();
+                self.patch(exit, done);
+                self.end_loop(top, done);
+            },
+            StmtKind::Function { name, params, body, locals } => {
+                let index = Self::index(self.program.functions.len(), "FUNCTIONS", name.span)?;
+                let locals = self.names(locals, name.span)?;
+                let function = Function {
+                    name: self.name(&name.name, name.span)?,
+                    arity: params.len() as u16,
+                    locals,
+                    start: 0,
+                };
+                self.program.functions.push(function);
+                self.pending.push((index as usize, body));
+                self.emit(Op::DefineFunction(index), name.span);
+            },
+            StmtKind::Return(Some(value)) => {
+                self.expression(value)?;
+                self.emit(Op::Return, stmt.span);
+            },
+            StmtKind::Global(_) => {},
+            StmtKind::Return(None) => {
+                self.emit(Op::ReturnNone, stmt.span);
+            },
+            StmtKind::Break => {
+                let jump = self.emit(Op::Jump(0), stmt.span);
+                self.loops.last_mut().unwrap().breaks.push(jump);
+            },
+            StmtKind::Continue => {
+                let jump = self.emit(Op::Jump(0), stmt.span);
+                self.loops.last_mut().unwrap().continues.push(jump);
+            },
+            // a call on its own line, whatever it returns is dropped
+            StmtKind::Expr(Expr { kind: ExprKind::Call { name, args }, span }) => {
+                self.call(name, args, *span, true)?;
+            },
+            StmtKind::Expr(expr) => {
+                self.expression(expr)?;
+                self.emit(Op::Pop, stmt.span);
+            },
+        }
+        Ok(())
+    }
+
+    // points the BREAKs and CONTINUEs of the innermost loop at its end and its next pass
+    fn end_loop(&mut self, next: u32, done: u32) {
+        let Some(finished) = self.loops.pop() else {
+            return;
+        };
+        for jump in finished.continues {
+            self.patch(jump, next);
+        }
+        for jump in finished.breaks {
+            self.patch(jump, done);
+        }
+    }
+
+    fn call(&mut self, name: &Ident, args: &[Expr], span: Span, discard: bool) -> Result<(), Error> {
+        for arg in args {
+            self.expression(arg)?;
+        }
+        let at = name.span;
+        let argc = Self::index(args.len(), "ARGUMENTS", at)?;
+        let name = self.name(&name.name, at)?;
+        let op = if discard { Op::CallDiscard { name, argc } } else { Op::Call { name, argc } };
+        self.emit_with(op, Origin { at, value: span });
+        Ok(())
+    }
+
+    // leaves the value of expr on the stack
+    fn expression(&mut self, expr: &Expr) -> Result<(), Error> {
+        match &expr.kind {
+            ExprKind::Literal(value) => {
+                let index = self.constant(value, expr.span)?;
+                self.emit(Op::Constant(index), expr.span);
+            },
+            ExprKind::Variable(name) => {
+                let op = match name.slot {
+                    Slot::Local(slot) => Op::LoadLocal(Self::local(slot, expr.span)?),
+                    _ => Op::Load(self.name(&name.name, expr.span)?),
+                };
+                self.emit(op, expr.span);
+            },
+            // errors about the value point at the parenthesis too
+            ExprKind::Grouping(inner) => {
+                self.expression(inner)?;
+                if let Some(origin) = self.program.origins.last_mut() {
+                    origin.value = expr.span;
+                }
+            },
+            ExprKind::Unary { op, operator, operand } => {
+                self.expression(operand)?;
+                let op = match op {
+                    UnaryOp::Not => Op::Not,
+                    UnaryOp::Negate => Op::Negate,
+                };
+                self.emit_with(op, Origin { at: *operator, value: expr.span });
+            },
+            ExprKind::Binary { op, operator, left, right } => {
+                self.expression(left)?;
+                self.expression(right)?;
+                self.emit_with(binary_op(*op), Origin { at: *operator, value: expr.span });
+            },
+            ExprKind::Call { name, args } => self.call(name, args, expr.span, false)?,
+        }
+        Ok(())
+    }
+}
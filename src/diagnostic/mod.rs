@@ -0,0 +1,61 @@
+//! Turns an [`Error`] into the text the command line and the REPL show.
+use crate::error::Error;
+
+// how many columns a tab takes up in the echoed line, the way rustc shows them
+const TAB_WIDTH: usize = 4;
+
+// the number of columns text takes up once its tabs are expanded
+fn columns(text: &str) -> usize {
+    text.chars().map(|c| if c == '\t' { TAB_WIDTH } else { 1 }).sum()
+}
+
+/// Formats an error the way rustc does, with the line it points at and a caret
+/// under its span:
+///
+/// ```text
+/// error[E0301]: X SYMBOL NOT FOUND
+///  --> file.jpo:2:7
+///   |
+/// 2 | PRINT X
+///   |       ^
+/// ```
+pub fn render(error: &Error, file: &str, source: &str) -> String {
+    let span = error.span();
+    let line = source.lines().nth(span.line.saturating_sub(1)).unwrap_or("");
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    let number = span.line.to_string();
+    let gutter = " ".repeat(number.len());
+
+    // the caret stops at the end of the line when the span runs over it
+    let start = span.offset.min(source.len());
+    let end = (span.offset + span.len).min(source.len());
+    let underlined = source.get(start..end).unwrap_or("");
+    let underlined = underlined.split('\n').next().unwrap_or("");
+    let width = columns(underlined).max(1);
+    // the caret has to line up with the expanded tabs before it
+    let before: String = line.chars().take(span.col.saturating_sub(1)).collect();
+    let line = line.replace('\t', &" ".repeat(TAB_WIDTH));
+
+    std::format!(
+        "error[{}]: {}\n{}--> {}:{}:{}\n{} |\n{} | {}\n{} | {}{}",
+        error.code(), error,
+        gutter, file, span.line, span.col,
+        gutter,
+        number, line,
+        gutter, " ".repeat(columns(&before)), "^".repeat(width),
+    )
+}
+
+/// For programs loaded from a .jpoc file, which only know the line an error comes
+/// from (and only when the file has a line table):
+///
+/// ```text
+/// error[E0307]: DIVISION BY ZERO
+///  --> file.jpoc:2
+/// ```
+pub fn render_compiled(error: &Error, file: &str) -> String {
+    match error.span().line {
+        0 => std::format!("error[{}]: {}\n --> {}", error.code(), error, file),
+        line => std::format!("error[{}]: {}\n --> {}:{}", error.code(), error, file, line),
+    }
+}
@@ -0,0 +1,407 @@
+//! Every error the interpreter reports, grouped by the stage that finds it.
+//!
+//! Each one knows the [`Span`] it points at and has a code that never changes, so
+//! tools can tell errors apart without looking at the message. The `Display` impls
+//! give the message, in uppercase.
+use crate::scanner::Span;
+use std::fmt;
+
+/// An error from any stage, see [`Error::code`] for how the codes are grouped.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Scan(ScanError),
+    Parse(ParseError),
+    Resolve(ResolveError),
+    Compile(CompileError),
+    Load(LoadError),
+    Runtime(RuntimeError),
+}
+
+/// Source text that isn't made of valid tokens.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScanError {
+    UnterminatedComment { span: Span },
+    UnterminatedString { span: Span },
+    InvalidEscape { escape: String, span: Span },
+    MalformedNumber { lexeme: String, span: Span },
+    NumberTooLarge { lexeme: String, span: Span },
+}
+
+/// Tokens that don't make a valid program or expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    UnexpectedToken { expected: String, found: String, span: Span },
+    MissingToken { expected: String, span: Span },
+    MissingEnd { block: String, span: Span },
+    UnmatchedEnd { span: Span },
+    MisplacedBranch { keyword: String, span: Span },
+    BranchAfterElse { keyword: String, span: Span },
+    OutsideLoop { keyword: String, span: Span },
+    ReturnOutsideFunction { span: Span },
+    UnclosedParen { span: Span },
+    InvalidParameter { function: String, span: Span },
+    DuplicateParameter { name: String, function: String, span: Span },
+    MissingOperand { operator: String, span: Span },
+    UnmatchedParen { span: Span },
+    TooDeep { max_nesting: usize, span: Span },
+}
+
+/// Names that can't be given a slot, found before the program runs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolveError {
+    UndefinedVariable { name: String, span: Span },
+}
+
+/// The bytecode indexes its tables with 16 bits, a program that needs more can't be
+/// compiled.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompileError {
+    TooMany { what: String, span: Span },
+}
+
+/// A compiled program read from a .jpoc file that can't be run. The file has no
+/// source to point at, so these errors have no span.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadError {
+    NotCompiled,
+    UnsupportedVersion { found: u16, supported: u16 },
+    Truncated { section: String },
+    Corrupt { reason: String },
+}
+
+/// Errors found while a program runs, by the interpreter or the VM.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    UndefinedSymbol { name: String, span: Span },
+    UndefinedFunction { name: String, span: Span },
+    ArityMismatch { function: String, expected: usize, found: usize, span: Span },
+    CallDepthExceeded { function: String, max_depth: usize, span: Span },
+    NoReturnValue { function: String, span: Span },
+    TypeMismatch { expected: String, found: String, span: Span },
+    DivisionByZero { span: Span },
+    ZeroStep { span: Span },
+    InvalidRepeatCount { count: f64, span: Span },
+    IntegerOverflow { span: Span },
+    PassLimitExceeded { max_passes: usize, span: Span },
+    StringTooLong { max_length: usize, span: Span },
+}
+
+impl Error {
+    /// The source the error points at. Errors loading a .jpoc file have no source
+    /// and give the default span, line 0.
+    pub fn span(&self) -> Span {
+        match self {
+            Error::Scan(error) => error.span(),
+            Error::Parse(error) => error.span(),
+            Error::Resolve(error) => error.span(),
+            Error::Compile(error) => error.span(),
+            Error::Load(_) => Span::default(),
+            Error::Runtime(error) => error.span(),
+        }
+    }
+
+    /// A code that stays the same between versions: `E01xx` for scan errors,
+    /// `E02xx` for parse errors, `E03xx` for runtime errors, `E04xx` for compile
+    /// errors, `E05xx` for load errors and `E06xx` for resolve errors.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Scan(error) => error.code(),
+            Error::Parse(error) => error.code(),
+            Error::Resolve(error) => error.code(),
+            Error::Compile(error) => error.code(),
+            Error::Load(error) => error.code(),
+            Error::Runtime(error) => error.code(),
+        }
+    }
+}
+
+impl ScanError {
+    pub fn span(&self) -> Span {
+        match self {
+            ScanError::UnterminatedComment { span }
+            | ScanError::UnterminatedString { span }
+            | ScanError::InvalidEscape { span, .. }
+            | ScanError::MalformedNumber { span, .. }
+            | ScanError::NumberTooLarge { span, .. } => *span,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ScanError::UnterminatedComment { .. } => "E0101",
+            ScanError::UnterminatedString { .. } => "E0102",
+            ScanError::InvalidEscape { .. } => "E0103",
+            ScanError::MalformedNumber { .. } => "E0104",
+            ScanError::NumberTooLarge { .. } => "E0105",
+        }
+    }
+}
+
+impl ParseError {
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnexpectedToken { span, .. }
+            | ParseError::MissingToken { span, .. }
+            | ParseError::MissingEnd { span, .. }
+            | ParseError::UnmatchedEnd { span }
+            | ParseError::MisplacedBranch { span, .. }
+            | ParseError::BranchAfterElse { span, .. }
+            | ParseError::OutsideLoop { span, .. }
+            | ParseError::ReturnOutsideFunction { span }
+            | ParseError::UnclosedParen { span }
+            | ParseError::InvalidParameter { span, .. }
+            | ParseError::DuplicateParameter { span, .. }
+            | ParseError::MissingOperand { span, .. }
+            | ParseError::UnmatchedParen { span }
+            | ParseError::TooDeep { span, .. } => *span,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::UnexpectedToken { .. } => "E0201",
+            ParseError::MissingToken { .. } => "E0202",
+            ParseError::MissingEnd { .. } => "E0203",
+            ParseError::UnmatchedEnd { .. } => "E0204",
+            ParseError::MisplacedBranch { .. } => "E0205",
+            ParseError::BranchAfterElse { .. } => "E0206",
+            ParseError::OutsideLoop { .. } => "E0207",
+            ParseError::ReturnOutsideFunction { .. } => "E0208",
+            ParseError::UnclosedParen { .. } => "E0209",
+            ParseError::InvalidParameter { .. } => "E0210",
+            ParseError::DuplicateParameter { .. } => "E0211",
+            ParseError::MissingOperand { .. } => "E0212",
+            ParseError::UnmatchedParen { .. } => "E0213",
+            ParseError::TooDeep { .. } => "E0214",
+        }
+    }
+}
+
+impl ResolveError {
+    pub fn span(&self) -> Span {
+        match self {
+            ResolveError::UndefinedVariable { span, .. } => *span,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ResolveError::UndefinedVariable { .. } => "E0601",
+        }
+    }
+}
+
+impl CompileError {
+    pub fn span(&self) -> Span {
+        match self {
+            CompileError::TooMany { span, .. } => *span,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            CompileError::TooMany { .. } => "E0401",
+        }
+    }
+}
+
+impl LoadError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            LoadError::NotCompiled => "E0501",
+            LoadError::UnsupportedVersion { .. } => "E0502",
+            LoadError::Truncated { .. } => "E0503",
+            LoadError::Corrupt { .. } => "E0504",
+        }
+    }
+}
+
+impl RuntimeError {
+    pub fn span(&self) -> Span {
+        match self {
+            RuntimeError::UndefinedSymbol { span, .. }
+            | RuntimeError::UndefinedFunction { span, .. }
+            | RuntimeError::ArityMismatch { span, .. }
+            | RuntimeError::CallDepthExceeded { span, .. }
+            | RuntimeError::NoReturnValue { span, .. }
+            | RuntimeError::TypeMismatch { span, .. }
+            | RuntimeError::DivisionByZero { span }
+            | RuntimeError::ZeroStep { span }
+            | RuntimeError::InvalidRepeatCount { span, .. }
+            | RuntimeError::IntegerOverflow { span }
+            | RuntimeError::PassLimitExceeded { span, .. }
+            | RuntimeError::StringTooLong { span, .. } => *span,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            RuntimeError::UndefinedSymbol { .. } => "E0301",
+            RuntimeError::UndefinedFunction { .. } => "E0302",
+            RuntimeError::ArityMismatch { .. } => "E0303",
+            RuntimeError::CallDepthExceeded { .. } => "E0304",
+            RuntimeError::NoReturnValue { .. } => "E0305",
+            RuntimeError::TypeMismatch { .. } => "E0306",
+            RuntimeError::DivisionByZero { .. } => "E0307",
+            RuntimeError::ZeroStep { .. } => "E0308",
+            RuntimeError::InvalidRepeatCount { .. } => "E0309",
+            RuntimeError::IntegerOverflow { .. } => "E0310",
+            RuntimeError::PassLimitExceeded { .. } => "E0311",
+            RuntimeError::StringTooLong { .. } => "E0312",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Scan(error) => write!(f, "{}", error),
+            Error::Parse(error) => write!(f, "{}", error),
+            Error::Resolve(error) => write!(f, "{}", error),
+            Error::Compile(error) => write!(f, "{}", error),
+            Error::Load(error) => write!(f, "{}", error),
+            Error::Runtime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScanError::UnterminatedComment { .. } => write!(f, "COMMENT NEVER CLOSED, EXPECTED '*/'"),
+            ScanError::UnterminatedString { .. } => write!(f, "STRING NEVER CLOSED, EXPECTED '\"'"),
+            ScanError::InvalidEscape { escape, .. } => {
+                write!(f, "UNKNOWN ESCAPE '{}' IN STRING, USE \\n, \\t, \\\", \\\\ OR \\u{{...}}", escape)
+            },
+            ScanError::MalformedNumber { lexeme, .. } => write!(f, "MALFORMED NUMBER '{}'", lexeme),
+            ScanError::NumberTooLarge { lexeme, .. } => write!(f, "NUMBER '{}' IS TOO LARGE", lexeme),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedToken { expected, found, .. } => {
+                write!(f, "EXPECTED {}, FOUND '{}'", expected, found.escape_default())
+            },
+            ParseError::MissingToken { expected, .. } => write!(f, "EXPECTED {}", expected),
+            ParseError::MissingEnd { block, .. } => write!(f, "MISSING END FOR '{}'", block),
+            ParseError::UnmatchedEnd { .. } => write!(f, "'END' WITHOUT A MATCHING BLOCK"),
+            ParseError::MisplacedBranch { keyword, .. } => write!(f, "'{}' WITHOUT A MATCHING 'IF'", keyword),
+            ParseError::BranchAfterElse { keyword, .. } => write!(f, "'{}' AFTER 'ELSE'", keyword),
+            ParseError::OutsideLoop { keyword, .. } => write!(f, "'{}' USED OUTSIDE OF A LOOP", keyword),
+            ParseError::ReturnOutsideFunction { .. } => write!(f, "'RETURN' USED OUTSIDE OF A FUNCTION"),
+            ParseError::UnclosedParen { .. } => write!(f, "MISSING ')'"),
+            ParseError::InvalidParameter { function, .. } => write!(f, "INVALID PARAMETER IN FUNCTION '{}'", function),
+            ParseError::DuplicateParameter { name, function, .. } => {
+                write!(f, "DUPLICATE PARAMETER '{}' IN FUNCTION '{}'", name, function)
+            },
+            ParseError::MissingOperand { operator, .. } => write!(f, "MISSING OPERAND FOR '{}'", operator),
+            ParseError::UnmatchedParen { .. } => write!(f, "')' WITHOUT A MATCHING '('"),
+            ParseError::TooDeep { max_nesting, .. } => write!(f, "NESTED DEEPER THAN {} LEVELS", max_nesting),
+        }
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResolveError::UndefinedVariable { name, .. } => {
+                write!(f, "{} IS NEVER GIVEN A VALUE. MAKE SURE TO INITIALIZE WITH 'LET'", name)
+            },
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompileError::TooMany { what, .. } => write!(f, "TOO MANY {} IN ONE PROGRAM", what),
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::NotCompiled => write!(f, "NOT A COMPILED PROGRAM, THE FILE DOESN'T START WITH 'JPOC'"),
+            LoadError::UnsupportedVersion { found, supported } => {
+                write!(f, "COMPILED PROGRAM HAS VERSION {}, ONLY VERSION {} CAN BE LOADED", found, supported)
+            },
+            LoadError::Truncated { section } => write!(f, "COMPILED PROGRAM ENDS IN THE MIDDLE OF THE {}", section),
+            LoadError::Corrupt { reason } => write!(f, "CORRUPT COMPILED PROGRAM: {}", reason),
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeError::UndefinedSymbol { name, .. } => {
+                write!(f, "{} SYMBOL NOT FOUND. MAKE SURE TO INITIALIZE WITH 'LET'", name)
+            },
+            RuntimeError::UndefinedFunction { name, .. } => write!(f, "FUNCTION '{}' NOT FOUND", name),
+            RuntimeError::ArityMismatch { function, expected, found, .. } => {
+                write!(f, "FUNCTION '{}' EXPECTS {} ARGUMENTS, FOUND {}", function, expected, found)
+            },
+            RuntimeError::CallDepthExceeded { function, max_depth, .. } => {
+                write!(f, "MAXIMUM CALL DEPTH OF {} EXCEEDED IN '{}'", max_depth, function)
+            },
+            RuntimeError::NoReturnValue { function, .. } => write!(f, "FUNCTION '{}' DID NOT RETURN A VALUE", function),
+            RuntimeError::TypeMismatch { expected, found, .. } => {
+                write!(f, "TYPE MISMATCH: EXPECTED {}, FOUND {}", expected, found)
+            },
+            RuntimeError::DivisionByZero { .. } => write!(f, "DIVISION BY ZERO"),
+            RuntimeError::ZeroStep { .. } => write!(f, "FOR LOOP STEP CAN NOT BE ZERO"),
+            RuntimeError::IntegerOverflow { .. } => write!(f, "INTEGER OVERFLOW, THE RESULT DOES NOT FIT IN 64 BITS"),
+            RuntimeError::InvalidRepeatCount { count, .. } => {
+                write!(f, "A STRING CAN ONLY BE REPEATED A WHOLE NUMBER OF TIMES, FOUND {}", count)
+            },
+            RuntimeError::PassLimitExceeded { max_passes, .. } => {
+                write!(f, "MAXIMUM OF {} LOOP PASSES EXCEEDED", max_passes)
+            },
+            RuntimeError::StringTooLong { max_length, .. } => {
+                write!(f, "A REPEATED STRING CAN BE AT MOST {} BYTES LONG", max_length)
+            },
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ScanError> for Error {
+    fn from(error: ScanError) -> Error {
+        Error::Scan(error)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Error {
+        Error::Parse(error)
+    }
+}
+
+impl From<ResolveError> for Error {
+    fn from(error: ResolveError) -> Error {
+        Error::Resolve(error)
+    }
+}
+
+impl From<CompileError> for Error {
+    fn from(error: CompileError) -> Error {
+        Error::Compile(error)
+    }
+}
+
+impl From<LoadError> for Error {
+    fn from(error: LoadError) -> Error {
+        Error::Load(error)
+    }
+}
+
+impl From<RuntimeError> for Error {
+    fn from(error: RuntimeError) -> Error {
+        Error::Runtime(error)
+    }
+}
@@ -1,317 +1,644 @@
-use crate::scanner::{Token, TokenType};
-use std::collections::HashMap;
+//! Runs a syntax tree from [`crate::parser::parse`] directly, the `--walk` mode of
+//! the command line and the engine behind the REPL.
+//!
+//! The helpers shared with [`crate::vm`] live here too, so both give the same
+//! values and the same errors.
+use crate::ast::{BinaryOp, Block, Expr, ExprKind, Ident, PrintItem, Slot, Stmt, StmtKind, UnaryOp};
+use crate::error::{Error, RuntimeError};
+use crate::resolver::Resolver;
+use crate::scanner::Span;
+use crate::value::Value;
+use std::cmp::Ordering;
+use std::io::{self, Write};
+use std::rc::Rc;
 
-#[derive(Debug)]
+/// How many FUNCTION calls can be active at once before giving up.
+pub const DEFAULT_MAX_DEPTH: usize = 200;
+// a ',' in PRINT moves to the next multiple of this column
+pub(crate) const PRINT_ZONE: usize = 14;
+/// The longest string `*` can make, in bytes, so a huge count is an error instead of
+/// an allocation that takes down the process.
+pub const MAX_REPEAT: usize = 16 * 1024 * 1024;
+
+// a FUNCTION keeps its resolved body, shared by every call
+struct Function {
+    arity: usize,
+    // how many local slots a call needs
+    locals: usize,
+    body: Block,
+}
+
+// how a block finished, loops stop on Break and start their next pass on Continue,
+// Return leaves every block up to the function call
 #[derive(PartialEq)]
-enum ValueType {
-    String,
-    Number,
-    Boolean,
+enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(Option<Value>),
 }
 
-#[derive(Debug)]
-#[allow(dead_code)]
-struct Symbol {
-    value: String,
-    v_type: ValueType,
-    //scope: String,
+pub(crate) fn mismatch(expected: &str, found: &Value, span: Span) -> Error {
+    RuntimeError::TypeMismatch {
+        expected: expected.to_string(),
+        found: found.type_name().to_string(),
+        span,
+    }.into()
 }
 
-trait IValueType {
-    fn value_type(self) -> Option<ValueType>;
+pub(crate) fn number((value, span): (Value, Span)) -> Result<Value, Error> {
+    match value {
+        Value::Integer(_) | Value::Float(_) => Ok(value),
+        other => Err(mismatch("NUMBER", &other, span)),
+    }
 }
 
-impl IValueType for &str {
-    fn value_type(self) -> Option<ValueType> {
-        if self == "TRUE" || self == "FALSE" {
-            return Some(ValueType::Boolean);
-        }
-        if self.parse::<f64>().is_ok() {
-            return Some(ValueType::Number);
-        }
-        return Some(ValueType::String);
+fn integer((value, span): (Value, Span)) -> Result<i64, Error> {
+    match value {
+        Value::Integer(integer) => Ok(integer),
+        other => Err(mismatch("INTEGER", &other, span)),
+    }
+}
+
+pub(crate) fn boolean((value, span): (Value, Span)) -> Result<bool, Error> {
+    match value {
+        Value::Boolean(boolean) => Ok(boolean),
+        other => Err(mismatch("BOOLEAN", &other, span)),
     }
 }
 
-fn expression_endpoint(tokens: &Vec<Token>, start: usize) -> Option<usize> {
-    let mut idx = start;
-    while tokens[idx].tk_type != TokenType::Newline && idx < tokens.len() {
-        idx += 1;
+// joins, repeats and compares strings. gives None when the operands aren't strings,
+// so the operator falls back to numbers
+fn string_operation(op: BinaryOp, operand1: &(Value, Span), operand2: &(Value, Span)) -> Result<Option<Value>, Error> {
+    let value = match (op, &operand1.0, &operand2.0) {
+        (BinaryOp::Add | BinaryOp::Concat, Value::String(left), Value::String(right)) => {
+            Value::String(std::format!("{}{}", left, right))
+        },
+        // '&' joins any two values the way PRINT shows them
+        (BinaryOp::Concat, left, right) => Value::String(std::format!("{}{}", left, right)),
+        (BinaryOp::Multiply, Value::String(text), count @ (Value::Integer(_) | Value::Float(_))) => {
+            repeat(text, count, operand2.1)?
+        },
+        (BinaryOp::Multiply, count @ (Value::Integer(_) | Value::Float(_)), Value::String(text)) => {
+            repeat(text, count, operand1.1)?
+        },
+        (BinaryOp::Greater, Value::String(left), Value::String(right)) => Value::Boolean(left > right),
+        (BinaryOp::GreaterEqual, Value::String(left), Value::String(right)) => Value::Boolean(left >= right),
+        (BinaryOp::Less, Value::String(left), Value::String(right)) => Value::Boolean(left < right),
+        (BinaryOp::LessEqual, Value::String(left), Value::String(right)) => Value::Boolean(left <= right),
+        _ => return Ok(None),
+    };
+    Ok(Some(value))
+}
+
+// span is the span of the count. the result is checked against MAX_REPEAT before
+// anything is allocated
+fn repeat(text: &str, count: &Value, span: Span) -> Result<Value, Error> {
+    let count = count.as_float().unwrap_or_default();
+    if count < 0.0 || count.fract() != 0.0 {
+        return Err(RuntimeError::InvalidRepeatCount { count, span }.into());
     }
-    if idx > start {
-        return Some(idx);
+    let times = count as usize;
+    if text.len().checked_mul(times).is_none_or(|length| length > MAX_REPEAT) {
+        return Err(RuntimeError::StringTooLong { max_length: MAX_REPEAT, span }.into());
     }
-    None
+    Ok(Value::String(text.repeat(times)))
 }
 
-fn precedence(operator: &str) -> u8 {
-    match TokenType::token_type(operator).unwrap() {
-        TokenType::NotOperator => 5,
-        TokenType::ExpOperator => 4,
-        TokenType::MultOperator | TokenType::DivOperator | TokenType::ModOperator => 3,
-        TokenType::AddOperator | TokenType::MinusOperator => 2,
-        TokenType::GreaterThan | TokenType::GreaterThanEq | TokenType::LessThan 
-            | TokenType::LessThanEq | TokenType::EqualsOperator | TokenType::NotEqualsOperator => 1,
-        TokenType::AndOperator | TokenType::OrOperator => 0,
-        _ => 0,
+// orders two numbers, integers exactly and anything with a float as floats
+fn compare(operand1: &Value, operand2: &Value) -> Option<Ordering> {
+    match (operand1, operand2) {
+        (Value::Integer(left), Value::Integer(right)) => Some(left.cmp(right)),
+        _ => operand1.as_float()?.partial_cmp(&operand2.as_float()?),
     }
 }
 
-fn evaluate_infix(symbol_table: &HashMap<&str, Symbol>, infix: &[Token]) -> Result<f64, String> {
-    let mut stack: Vec<&Token> = Vec::new();
-    let mut postfix: Vec<&str> = Vec::new();
-    
-    // convert to postfix
-    for token in infix {
-        let res = token.lexeme.parse::<f64>();
-        if token.tk_type == TokenType::Identifier {
-            let value = symbol_table.get(token.lexeme);
-            if value.is_some() {
-                postfix.push(&value.unwrap().value);
-            } else {
-                let message = std::format!("{} SYMBOL NOT FOUND", token.lexeme);
-                return Err(message);
-            }
+// arithmetic on two numbers. integers give integers and fail when the result doesn't
+// fit, an operation with a float gives a float. '/' always gives a float
+fn arithmetic(op: BinaryOp, operator: Span, operand1: Value, operand2: Value) -> Result<Value, Error> {
+    if let (Value::Integer(left), Value::Integer(right)) = (&operand1, &operand2) {
+        let (left, right) = (*left, *right);
+        let result = match op {
+            BinaryOp::Add => left.checked_add(right),
+            BinaryOp::Subtract => left.checked_sub(right),
+            BinaryOp::Multiply => left.checked_mul(right),
+            BinaryOp::IntDivide | BinaryOp::Modulo if right == 0 => {
+                return Err(RuntimeError::DivisionByZero { span: operator }.into());
+            },
+            BinaryOp::IntDivide => left.checked_div(right),
+            // the only remainder that overflows, MIN MOD -1, is 0
+            BinaryOp::Modulo => Some(left.wrapping_rem(right)),
+            BinaryOp::Power if right >= 0 => left.checked_pow(u32::try_from(right).unwrap_or(u32::MAX)),
+            // a quotient or a negative power can have a fraction
+            _ => return arithmetic(op, operator, Value::Float(left as f64), Value::Float(right as f64)),
+        };
+        return match result {
+            Some(result) => Ok(Value::Integer(result)),
+            None => Err(RuntimeError::IntegerOverflow { span: operator }.into()),
+        };
+    }
+    let (operand1, operand2) = (operand1.as_float().unwrap_or_default(), operand2.as_float().unwrap_or_default());
+    let value = match op {
+        BinaryOp::Add => operand1 + operand2,
+        BinaryOp::Subtract => operand1 - operand2,
+        BinaryOp::Multiply => operand1 * operand2,
+        BinaryOp::Divide | BinaryOp::Modulo if operand2 == 0.0 => {
+            return Err(RuntimeError::DivisionByZero { span: operator }.into());
+        },
+        BinaryOp::Divide => operand1 / operand2,
+        BinaryOp::Modulo => operand1 % operand2,
+        _ => operand1.powf(operand2),
+    };
+    Ok(Value::Float(value))
+}
+
+// applies a binary operator to two values, each with the span of the expression it came from.
+// operator is the span of the operator token
+pub(crate) fn binary(op: BinaryOp, operator: Span, operand1: (Value, Span), operand2: (Value, Span)) -> Result<Value, Error> {
+    // two integers are the common case in loops and counters, so they skip the
+    // checks for strings and mixed types below
+    if let (Value::Integer(left), Value::Integer(right)) = (&operand1.0, &operand2.0) {
+        let (left, right) = (*left, *right);
+        match op {
+            BinaryOp::Equal => return Ok(Value::Boolean(left == right)),
+            BinaryOp::NotEqual => return Ok(Value::Boolean(left != right)),
+            BinaryOp::Greater => return Ok(Value::Boolean(left > right)),
+            BinaryOp::GreaterEqual => return Ok(Value::Boolean(left >= right)),
+            BinaryOp::Less => return Ok(Value::Boolean(left < right)),
+            BinaryOp::LessEqual => return Ok(Value::Boolean(left <= right)),
+            BinaryOp::And | BinaryOp::Or | BinaryOp::Concat => {},
+            _ => return arithmetic(op, operator, Value::Integer(left), Value::Integer(right)),
         }
-        else if res.is_ok() {
-            postfix.push(token.lexeme);
-        } else if token.tk_type == TokenType::LParen {
-            stack.push(token);
-        } else if token.tk_type == TokenType::RParen {
-            while stack.len() > 0 && stack.last().unwrap().tk_type != TokenType::LParen {
-                postfix.push(stack.pop().unwrap().lexeme);
+    }
+    let value = match op {
+        BinaryOp::And | BinaryOp::Or => {
+            let operand2 = boolean(operand2)?;
+            let operand1 = boolean(operand1)?;
+            match op {
+                BinaryOp::And => Value::Boolean(operand1 && operand2),
+                _ => Value::Boolean(operand1 || operand2),
             }
-            stack.pop();
-        } else {
-            while stack.len() > 0 && precedence(stack.last().unwrap().lexeme) >= precedence(token.lexeme) {
-                postfix.push(stack.pop().unwrap().lexeme);
+        },
+        BinaryOp::Equal | BinaryOp::NotEqual => {
+            let ((operand1, _), (operand2, span)) = (operand1, operand2);
+            // only values of the same type can be compared, an integer and a float
+            // are equal when they are the same number
+            let equal = match (operand1.as_float(), operand2.as_float()) {
+                (Some(_), Some(_)) => compare(&operand1, &operand2) == Some(Ordering::Equal),
+                _ if operand1.type_name() != operand2.type_name() => {
+                    return Err(mismatch(operand1.type_name(), &operand2, span));
+                },
+                _ => operand1 == operand2,
+            };
+            Value::Boolean(equal == (op == BinaryOp::Equal))
+        },
+        // '\' only divides integers
+        BinaryOp::IntDivide => {
+            let operand2 = integer(operand2)?;
+            let operand1 = integer(operand1)?;
+            arithmetic(op, operator, Value::Integer(operand1), Value::Integer(operand2))?
+        },
+        _ => {
+            if let Some(value) = string_operation(op, &operand1, &operand2)? {
+                return Ok(value);
             }
-            stack.push(token);
-        }
+            let operand2 = number(operand2)?;
+            let operand1 = number(operand1)?;
+            match op {
+                BinaryOp::Greater | BinaryOp::GreaterEqual | BinaryOp::Less | BinaryOp::LessEqual => {
+                    let order = compare(&operand1, &operand2);
+                    Value::Boolean(match op {
+                        BinaryOp::Greater => order == Some(Ordering::Greater),
+                        BinaryOp::GreaterEqual => matches!(order, Some(Ordering::Greater | Ordering::Equal)),
+                        BinaryOp::Less => order == Some(Ordering::Less),
+                        _ => matches!(order, Some(Ordering::Less | Ordering::Equal)),
+                    })
+                },
+                _ => arithmetic(op, operator, operand1, operand2)?,
+            }
+        },
+    };
+    Ok(value)
+}
+
+// -X, operator is the span of the '-'
+pub(crate) fn negate(operator: Span, operand: (Value, Span)) -> Result<Value, Error> {
+    match number(operand)? {
+        Value::Integer(integer) => match integer.checked_neg() {
+            Some(negated) => Ok(Value::Integer(negated)),
+            None => Err(RuntimeError::IntegerOverflow { span: operator }.into()),
+        },
+        other => Ok(Value::Float(-other.as_float().unwrap_or_default())),
     }
-    while stack.len() > 0 {
-        postfix.push(stack.pop().unwrap().lexeme);
+}
+
+// whether a FOR loop with this counter, bound and step runs another pass
+pub(crate) fn for_continues(counter: &Value, to: &Value, step: &Value) -> bool {
+    match compare(step, &Value::Integer(0)) {
+        Some(Ordering::Greater) => matches!(compare(counter, to), Some(Ordering::Less | Ordering::Equal)),
+        Some(Ordering::Less) => matches!(compare(counter, to), Some(Ordering::Greater | Ordering::Equal)),
+        _ => false,
     }
-    let result = evaluate_postfix(&postfix);
-    Ok(result)
 }
 
-fn evaluate_postfix(postfix: &Vec<&str>) -> f64 {
-    let mut stack: Vec<f64> = Vec::new();
-    
-    for op in postfix {
-        let res = op.parse::<f64>();
-        if res.is_ok() {
-            stack.push(res.unwrap());
-        } else {
-            match TokenType::token_type(op) {
-                Some(TokenType::TrueLiteral) => {
-                    stack.push(1.0);
-                }
-                Some(TokenType::FalseLiteral) => {
-                    stack.push(0.0);
-                }
-                Some(TokenType::NotOperator) => {
-                    let operand1 = stack.pop().unwrap() as i32 != 0;
-                    stack.push((!operand1) as i32 as f64);
-                }
-                Some(TokenType::AndOperator) => {
-                    let operand2 = stack.pop().unwrap() as i32 != 0;
-                    let operand1 = stack.pop().unwrap() as i32 != 0;
-                    stack.push((operand1 && operand2) as i32 as f64);
-                }
-                Some(TokenType::OrOperator) => {
-                    let operand2 = stack.pop().unwrap() as i32 != 0;
-                    let operand1 = stack.pop().unwrap() as i32 != 0;
-                    stack.push((operand1 || operand2) as i32 as f64);
-                }
-                Some(TokenType::GreaterThan) => {
-                    let operand2 = stack.pop().unwrap();
-                    let operand1 = stack.pop().unwrap();
-                    stack.push((operand1 > operand2) as i32 as f64);
-                }
-                Some(TokenType::GreaterThanEq) => {
-                    let operand2 = stack.pop().unwrap();
-                    let operand1 = stack.pop().unwrap();
-                    stack.push((operand1 >= operand2) as i32 as f64);
-                }
-                Some(TokenType::LessThan) => {
-                    let operand2 = stack.pop().unwrap();
-                    let operand1 = stack.pop().unwrap();
-                    stack.push((operand1 < operand2) as i32 as f64);
-                }
-                Some(TokenType::LessThanEq) => {
-                    let operand2 = stack.pop().unwrap();
-                    let operand1 = stack.pop().unwrap();
-                    stack.push((operand1 <= operand2) as i32 as f64);
-                }
-                Some(TokenType::AddOperator) => {
-                    let operand2 = stack.pop().unwrap();
-                    let operand1 = stack.pop().unwrap();
-                    stack.push(operand1 + operand2);
-                }
-                Some(TokenType::MinusOperator) => {
-                    let operand2 = stack.pop().unwrap();
-                    let operand1 = stack.pop().unwrap();
-                    stack.push(operand1 - operand2);
-                }
-                Some(TokenType::MultOperator) => {
-                    let operand2 = stack.pop().unwrap();
-                    let operand1 = stack.pop().unwrap();
-                    stack.push(operand1 * operand2);
-                }
-                Some(TokenType::DivOperator) => {
-                    let operand2 = stack.pop().unwrap();
-                    let operand1 = stack.pop().unwrap();
-                    stack.push(operand1 / operand2);
-                }
-                Some(TokenType::ModOperator) => {
-                    let operand2 = stack.pop().unwrap();
-                    let operand1 = stack.pop().unwrap();
-                    stack.push(operand1 % operand2);
+// the counter of the next pass of a FOR loop, None when the loop is done. a step past
+// the last pass that doesn't fit in an integer also ends the loop, since the counter
+// is then past any integer bound
+pub(crate) fn for_step(span: Span, counter: &Value, to: &Value, step: &Value) -> Result<Option<Value>, Error> {
+    match binary(BinaryOp::Add, span, (counter.clone(), span), (step.clone(), span)) {
+        Ok(next) => Ok(Some(next)),
+        Err(Error::Runtime(RuntimeError::IntegerOverflow { .. })) if matches!(to, Value::Integer(_)) => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+// adds text to the pending PRINT output and keeps track of the column
+pub(crate) fn write(column: &mut usize, output: &mut String, text: &str) {
+    match text.rfind('\n') {
+        Some(newline) => *column = text[newline + 1..].chars().count(),
+        None => *column += text.chars().count(),
+    }
+    output.push_str(text);
+}
+
+/// A tree-walking interpreter. Variables, functions and the cursor column of PRINT
+/// are kept from one program to the next, the way the REPL needs them.
+pub struct Interpreter {
+    // slots of every name seen so far, kept so the REPL can run one input after the other
+    resolver: Resolver,
+    globals: Vec<Option<Value>>,
+    // locals of the blocks of the top level code
+    main: Vec<Option<Value>>,
+    // locals of every active FUNCTION call, the last one is the current call
+    frames: Vec<Vec<Option<Value>>>,
+    functions: Vec<Option<Rc<Function>>>,
+    max_depth: usize,
+    // loop passes allowed in one run, and how many the current run has made
+    max_passes: Option<usize>,
+    passes: usize,
+    // column PRINT left the cursor at, only non-zero after a trailing ',' or ';'
+    column: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn undefined(name: &Ident) -> Error {
+    RuntimeError::UndefinedSymbol { name: name.name.clone(), span: name.span }.into()
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        Interpreter {
+            resolver: Resolver::new(),
+            globals: Vec::new(),
+            main: Vec::new(),
+            frames: Vec::new(),
+            functions: Vec::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_passes: None,
+            passes: 0,
+            column: 0,
+        }
+    }
+
+    /// Limits how deep FUNCTION calls (and recursion) can go.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// Stops a run once its FOR and WHILE loops went back for another pass this many
+    /// times, so a program that never ends can still be run. There is no limit by
+    /// default.
+    pub fn set_max_passes(&mut self, max_passes: usize) {
+        self.max_passes = Some(max_passes);
+    }
+
+    /// Forgets every variable and function, used by the REPL `:reset` command.
+    pub fn reset(&mut self) {
+        self.resolver = Resolver::new();
+        self.globals.clear();
+        self.main.clear();
+        self.frames.clear();
+        self.functions.clear();
+    }
+
+    /// Global variables that have a value, sorted by name.
+    pub fn variables(&self) -> Vec<(&str, &Value)> {
+        let mut variables: Vec<(&str, &Value)> = self.resolver
+            .globals()
+            .iter()
+            .zip(&self.globals)
+            .filter_map(|(name, value)| Some((name.as_str(), value.as_ref()?)))
+            .collect();
+        variables.sort_by(|a, b| a.0.cmp(b.0));
+        variables
+    }
+
+    // makes room for the slots the resolver handed out
+    fn grow(&mut self) {
+        self.globals.resize(self.resolver.globals().len(), None);
+        self.functions.resize(self.resolver.functions(), None);
+    }
+
+    /// Evaluates a bare expression typed at the REPL, see [`crate::parser::parse_expression`].
+    /// A call to a FUNCTION that doesn't return anything gives `None`.
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Option<Value>, Error> {
+        let mut expr = expr.clone();
+        self.resolver.resolve_expression(&mut expr)?;
+        self.grow();
+        self.passes = 0;
+        match &expr.kind {
+            ExprKind::Call { name, args } => self.call_function(name, args),
+            _ => Ok(Some(self.evaluate_expr(&expr)?)),
+        }
+    }
+
+    // where the variable the resolver pointed a name at is stored
+    fn variable(&mut self, name: &Ident) -> Option<&mut Option<Value>> {
+        match name.slot {
+            Slot::Local(slot) => self.frames.last_mut().unwrap_or(&mut self.main).get_mut(slot),
+            Slot::Global(slot) => self.globals.get_mut(slot),
+            Slot::Unresolved => None,
+        }
+    }
+
+    fn lookup(&mut self, name: &Ident) -> Result<Value, Error> {
+        match self.variable(name) {
+            Some(Some(value)) => Ok(value.clone()),
+            _ => Err(undefined(name)),
+        }
+    }
+
+    // LET gives the variable a value whether it had one or not
+    fn declare(&mut self, name: &Ident, value: Value) {
+        if let Some(variable) = self.variable(name) {
+            *variable = Some(value);
+        }
+    }
+
+    // assigning without LET only works on a variable that already has a value
+    fn assign(&mut self, name: &Ident, value: Value) -> Result<(), Error> {
+        let target = match self.variable(name) {
+            Some(Some(target)) => Some(target),
+            _ => None,
+        };
+        match target {
+            Some(target) => {
+                *target = value;
+                Ok(())
+            },
+            None => Err(undefined(name)),
+        }
+    }
+
+    fn evaluate_expr(&mut self, expr: &Expr) -> Result<Value, Error> {
+        match &expr.kind {
+            ExprKind::Literal(value) => Ok(value.clone()),
+            ExprKind::Variable(name) => self.lookup(name),
+            ExprKind::Grouping(inner) => self.evaluate_expr(inner),
+            ExprKind::Unary { op, operator, operand } => {
+                let value = (self.evaluate_expr(operand)?, operand.span);
+                match op {
+                    UnaryOp::Not => Ok(Value::Boolean(!boolean(value)?)),
+                    UnaryOp::Negate => negate(*operator, value),
                 }
-                Some(TokenType::ExpOperator) => {
-                    let operand2 = stack.pop().unwrap();
-                    let operand1 = stack.pop().unwrap();
-                    stack.push(operand1.powf(operand2));
+            },
+            ExprKind::Binary { op, operator, left, right } => {
+                let operand1 = (self.evaluate_expr(left)?, left.span);
+                let operand2 = (self.evaluate_expr(right)?, right.span);
+                binary(*op, *operator, operand1, operand2)
+            },
+            ExprKind::Call { name, args } => match self.call_function(name, args)? {
+                Some(value) => Ok(value),
+                None => Err(RuntimeError::NoReturnValue { function: name.name.clone(), span: expr.span }.into()),
+            },
+        }
+    }
+
+    // runs the body of a FUNCTION in a new frame that only holds its parameters.
+    // the arguments are evaluated in the caller's frame first
+    fn call_function(&mut self, name: &Ident, args: &[Expr]) -> Result<Option<Value>, Error> {
+        let mut values = Vec::new();
+        for arg in args {
+            values.push(self.evaluate_expr(arg)?);
+        }
+        let defined = match name.slot {
+            Slot::Global(slot) => self.functions[slot].clone(),
+            _ => None,
+        };
+        let function = match defined {
+            Some(function) => function,
+            None => return Err(RuntimeError::UndefinedFunction { name: name.name.clone(), span: name.span }.into()),
+        };
+        if args.len() != function.arity {
+            return Err(RuntimeError::ArityMismatch {
+                function: name.name.clone(),
+                expected: function.arity,
+                found: args.len(),
+                span: name.span,
+            }.into());
+        }
+        if self.frames.len() >= self.max_depth {
+            let function = name.name.clone();
+            return Err(RuntimeError::CallDepthExceeded { function, max_depth: self.max_depth, span: name.span }.into());
+        }
+
+        let mut slots: Vec<Option<Value>> = values.into_iter().map(Some).collect();
+        slots.resize(function.locals, None);
+        self.frames.push(slots);
+        let flow = self.execute_block(&function.body);
+        self.frames.pop();
+        match flow? {
+            Flow::Return(value) => Ok(value),
+            _ => Ok(None),
+        }
+    }
+
+    // FOR <id> IN <start> TO <end> [STEP <step>]
+    // the end bound is inclusive, like in classic BASIC. the bounds and the step are
+    // evaluated once before the first pass, so assigning to the loop variable
+    // inside the body does not change how many times the loop runs. span is the span
+    // of the whole loop, where an overflowing counter is reported
+    fn execute_for(&mut self, span: Span, variable: &Ident, start: &Expr, end: &Expr, step: Option<&Expr>, body: &[Stmt]) -> Result<Flow, Error> {
+        let from = self.loop_number(start, "START")?;
+        let to = self.loop_number(end, "BOUND")?;
+        let step = match step {
+            Some(step) => {
+                let span = step.span;
+                let step = self.loop_number(step, "STEP")?;
+                if step.as_float() == Some(0.0) {
+                    return Err(RuntimeError::ZeroStep { span }.into());
                 }
-                _ => {}
+                step
+            },
+            None => Value::Integer(1),
+        };
+
+        // the counter stays an integer as long as the start and the step are integers
+        let mut counter = from;
+        while for_continues(&counter, &to, &step) {
+            self.declare(variable, counter.clone());
+            match self.execute_block(body)? {
+                Flow::Break => break,
+                Flow::Return(value) => return Ok(Flow::Return(value)),
+                _ => {},
             }
+            self.pass(span)?;
+            counter = match for_step(span, &counter, &to, &step)? {
+                Some(next) => next,
+                None => break,
+            };
         }
+        Ok(Flow::Normal)
     }
 
-    stack.pop().unwrap()
-}
+    // WHILE <condition>
+    // the condition is evaluated again before every pass
+    fn execute_while(&mut self, span: Span, condition: &Expr, body: &[Stmt]) -> Result<Flow, Error> {
+        while self.condition(condition)? {
+            match self.execute_block(body)? {
+                Flow::Break => break,
+                Flow::Return(value) => return Ok(Flow::Return(value)),
+                _ => {},
+            }
+            self.pass(span)?;
+        }
+        Ok(Flow::Normal)
+    }
 
-pub fn execute(tokens: &Vec<Token>) -> Result<(), String> {
-    let mut symbol_table: HashMap<&str, Symbol> = HashMap::new();
-    let mut idx: usize = 0;
-    let mut skip_scope = false;
-    let mut look_elif = false;
-
-    while idx < tokens.len() {
-        loop { // 'loop' for breaking the match early
-        match tokens[idx].tk_type {
-            TokenType::Initialize => {
-                if skip_scope { break }
-                idx += 1;
-                if tokens[idx].tk_type == TokenType::Identifier {
-                    let id = &tokens[idx];
-                    idx += 1;
-                    if tokens[idx].tk_type == TokenType::Assign {
-                        idx += 1;
-                        let end = expression_endpoint(tokens, idx).unwrap();
-                        if end - idx > 1 {
-                            let expression_tokens = &tokens[idx..end];
-                            let value = evaluate_infix(&symbol_table, &expression_tokens);
-                            symbol_table.insert(id.lexeme, Symbol {
-                                value: value.unwrap().to_string(), 
-                                v_type: ValueType::Number,
-                            });
-                        } else {
-                            let value = tokens[idx].lexeme;
-                            let value_type = value.value_type();
-                            symbol_table.insert(id.lexeme, Symbol {
-                                value: value.to_string(), 
-                                v_type: value_type.unwrap(),
-                            });
-                        }
-                        idx = end;
-                    } else {
-                        let error_message = std::format!("EXPECTED ASSIGN OPERATOR, FOUND '{}'", tokens[idx].lexeme);
-                        return Err(error_message);
-                    }
-                } else {
-                    let error_message = std::format!("EXPECTED IDENTIFIER SYMBOL, FOUND '{}'", tokens[idx].lexeme);
-                    return Err(error_message);
-                }
+    // counts a loop going back for another pass, see set_max_passes
+    fn pass(&mut self, span: Span) -> Result<(), Error> {
+        self.passes += 1;
+        match self.max_passes {
+            Some(max_passes) if self.passes > max_passes => Err(RuntimeError::PassLimitExceeded { max_passes, span }.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn loop_number(&mut self, expr: &Expr, part: &str) -> Result<Value, Error> {
+        match self.evaluate_expr(expr)? {
+            number @ (Value::Integer(_) | Value::Float(_)) => Ok(number),
+            other => Err(mismatch(&std::format!("NUMBER FOR THE FOR LOOP {}", part), &other, expr.span)),
+        }
+    }
+
+    // IF, ELIF and WHILE only accept TRUE or FALSE
+    fn condition(&mut self, expr: &Expr) -> Result<bool, Error> {
+        let value = self.evaluate_expr(expr)?;
+        boolean((value, expr.span))
+    }
+
+    // PRINT <item> [, <item>] [; <item>] ...
+    // ',' moves to the next print zone and ';' leaves no space between items.
+    // a ',' or ';' at the end keeps the cursor on the same line for the next PRINT
+    fn print(&mut self, items: &[PrintItem]) -> Result<(), Error> {
+        let mut output = String::new();
+        for item in items {
+            match item {
+                PrintItem::Expr(expr) => {
+                    let value = self.evaluate_expr(expr)?;
+                    write(&mut self.column, &mut output, &value.to_string());
+                },
+                PrintItem::Zone => {
+                    let padding = PRINT_ZONE - self.column % PRINT_ZONE;
+                    write(&mut self.column, &mut output, &" ".repeat(padding));
+                },
+                PrintItem::Join => {},
+            }
+        }
+
+        let mut stdout = io::stdout();
+        if matches!(items.last(), None | Some(PrintItem::Expr(_))) {
+            output.push('\n');
+            self.column = 0;
+        }
+        let _ = stdout.write_all(output.as_bytes());
+        let _ = stdout.flush();
+        Ok(())
+    }
+
+    /// Runs a program produced by [`crate::parser::parse`]. Every name is resolved
+    /// before the first statement runs, so an undefined variable stops it early.
+    pub fn execute(&mut self, program: &[Stmt]) -> Result<(), Error> {
+        let mut program = program.to_vec();
+        let locals = self.resolver.resolve(&mut program)?;
+        self.grow();
+        self.main = vec![None; locals.len()];
+        self.passes = 0;
+        self.execute_block(&program)?;
+        Ok(())
+    }
+
+    fn execute_block(&mut self, block: &[Stmt]) -> Result<Flow, Error> {
+        for stmt in block {
+            let flow = self.execute_stmt(stmt)?;
+            if flow != Flow::Normal {
+                return Ok(flow);
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn execute_stmt(&mut self, stmt: &Stmt) -> Result<Flow, Error> {
+        match &stmt.kind {
+            StmtKind::Let { name, value } => {
+                let value = self.evaluate_expr(value)?;
+                self.declare(name, value);
             },
-            TokenType::Identifier => {
-                if skip_scope { break }
-                let symbol = tokens[idx].lexeme;
-                let id = symbol_table.get(symbol);
-                if id.is_none() {
-                    let error_message = std::format!("{} SYMBOL NOT FOUND. MAKE SURE TO INITIALIZE WITH 'LET'", symbol);
-                    return Err(error_message);
-                }
-                idx += 1;
-                if tokens[idx].tk_type == TokenType::Assign {
-                    idx += 1;
-                    let end = expression_endpoint(tokens, idx).unwrap();
-                    let tokens = &tokens[idx..end];
-                    let result = evaluate_infix(&symbol_table, &tokens);
-                    match result {
-                        Ok(value) => {
-                            symbol_table.insert(symbol, Symbol {
-                                value: value.to_string(), 
-                                v_type: ValueType::Number
-                            });
-                        },
-                        Err(message) => {
-                            return Err(message);
-                        }
-                    }
-                    idx = end;
-                } else {
-                    let error_message = std::format!("EXPECTED ASSIGN OPERATOR, FOUND '{}'", tokens[idx].lexeme);
-                    return Err(error_message);
-                }
+            StmtKind::Assign { name, value } => {
+                let value = self.evaluate_expr(value)?;
+                self.assign(name, value)?;
             },
-            TokenType::Output => {
-                if skip_scope { break }
-                idx += 1;
-                let tk_print = &tokens[idx];
-                if tk_print.tk_type == TokenType::Identifier {
-                    let id = symbol_table.get(tk_print.lexeme);
-                    if id.is_none() {
-                        let error_message = std::format!("{} SYMBOL NOT FOUND. MAKE SURE TO INITIALIZE WITH 'LET'", tk_print.lexeme);
-                        return Err(error_message);
+            StmtKind::Print(items) => self.print(items)?,
+            // only the first branch whose condition holds is run
+            StmtKind::If { branches, otherwise } => {
+                for branch in branches {
+                    if self.condition(&branch.condition)? {
+                        return self.execute_block(&branch.body);
                     }
-                    // remove quotes if string
-                    let value = id.unwrap().value.trim_matches('"');
-                    println!("{}", value);
-                } else {
-                    let value = tk_print.lexeme.trim_matches('"');
-                    println!("{}", value);
+                }
+                if let Some(body) = otherwise {
+                    return self.execute_block(body);
                 }
             },
-            TokenType::If => {
-                if skip_scope { break }
-                idx += 1;
-                let end = expression_endpoint(tokens, idx).unwrap();
-                let tokens = &tokens[idx..end];
-                let result = evaluate_infix(&symbol_table, &tokens);
-                match result {
-                    Ok(condition) => {
-                        if condition == 1.0 {
-                            skip_scope = false;
-                            look_elif = false;
-                        } else {
-                            skip_scope = true;
-                            look_elif = true;
-                        }
-                    },
-                    Err(message) => {
-                        return Err(message);
-                    }
+            StmtKind::For { variable, start, end, step, body } => {
+                return self.execute_for(stmt.span, variable, start, end, step.as_ref(), body);
+            },
+            StmtKind::While { condition, body } => return self.execute_while(stmt.span, condition, body),
+            // the function is usable once its definition has been run
+            StmtKind::Function { name, params, body, locals } => {
+                let function = Function {
+                    arity: params.len(),
+                    locals: locals.len(),
+                    body: body.clone(),
+                };
+                if let Slot::Global(slot) = name.slot {
+                    self.functions[slot] = Some(Rc::new(function));
                 }
             },
-            TokenType::Else => {
-                idx += 1;
-                // will skip ELSE if did IF, otherwise it wont
-                skip_scope = !skip_scope;
+            StmtKind::Return(value) => {
+                let value = match value {
+                    Some(value) => Some(self.evaluate_expr(value)?),
+                    None => None,
+                };
+                return Ok(Flow::Return(value));
+            },
+            StmtKind::Break => return Ok(Flow::Break),
+            StmtKind::Continue => return Ok(Flow::Continue),
+            // a call on its own line, whatever it returns is dropped
+            StmtKind::Expr(Expr { kind: ExprKind::Call { name, args }, .. }) => {
+                self.call_function(name, args)?;
             },
-            TokenType::EndScope => {
-                idx += 1;
-                skip_scope = false;
+            StmtKind::Expr(expr) => {
+                self.evaluate_expr(expr)?;
             },
-            _ => {},
+            // only tells the resolver where names point
+            StmtKind::Global(_) => {},
         }
-        break;}
-        idx += 1;
+        Ok(Flow::Normal)
     }
-    //for (symbol, data) in symbol_table {
-      //  println!("D: {} = {}", symbol, data.value);
-    //}
-    Ok(())
 }
@@ -0,0 +1,474 @@
+//! Reads and writes compiled programs as `.jpoc` files.
+//!
+//! Every number is little endian. A file is laid out as:
+//!
+//! ```text
+//! magic        b"JPOC"
+//! version      u16, see VERSION
+//! flags        u16, bit 0 is set when the line table is present
+//! constants    u32 count, then per constant a tag u8 and its value:
+//!              0 string (u32 length, UTF-8 bytes), 1 float (f64), 2 boolean (u8),
+//!              3 integer (i64)
+//! names        u32 count, then per name a u32 length and UTF-8 bytes
+//! locals       u16 count, then a u16 name for every local slot of the top level code
+//! functions    u32 count, then per function its name u16, arity u16,
+//!              u16 count of locals, a u16 name for every local and its start u32
+//! code         u32 count, then per instruction an opcode u8 and its operands
+//! line table   only with flag bit 0: u32 count, then runs of (first instruction u32,
+//!              line u32), a run lasts until the next one starts
+//! ```
+//!
+//! [`decode`] checks every index and the height of the stack before each
+//! instruction, so a damaged file is rejected before the VM runs any of it.
+use crate::bytecode::{Function, LoopPart, Op, Origin, Program};
+use crate::error::{Error, LoadError};
+use crate::scanner::Span;
+use crate::value::Value;
+
+/// The first four bytes of every compiled program.
+pub const MAGIC: &[u8; 4] = b"JPOC";
+/// The version written by [`encode`] and the only one [`decode`] accepts.
+pub const VERSION: u16 = 4;
+
+const LINE_TABLE: u16 = 1;
+
+fn opcode(op: Op) -> u8 {
+    match op {
+        Op::Constant(_) => 0,
+        Op::Load(_) => 1,
+        Op::Store(_) => 2,
+        Op::Define(_) => 3,
+        Op::LoadLocal(_) => 4,
+        Op::StoreLocal(_) => 5,
+        Op::DefineLocal(_) => 6,
+        Op::Pop => 7,
+        Op::Not => 8,
+        Op::Negate => 9,
+        Op::Add => 10,
+        Op::Subtract => 11,
+        Op::Multiply => 12,
+        Op::Divide => 13,
+        Op::Modulo => 14,
+        Op::Power => 15,
+        Op::Concat => 16,
+        Op::Equal => 17,
+        Op::NotEqual => 18,
+        Op::Less => 19,
+        Op::LessEqual => 20,
+        Op::Greater => 21,
+        Op::GreaterEqual => 22,
+        Op::And => 23,
+        Op::Or => 24,
+        Op::Jump(_) => 25,
+        Op::JumpIfFalse(_) => 26,
+        Op::ForNumber(_) => 27,
+        Op::ForTest(_) => 28,
+        Op::ForStep(_) => 29,
+        Op::DefineFunction(_) => 30,
+        Op::Call { .. } => 31,
+        Op::CallDiscard { .. } => 32,
+        Op::Return => 33,
+        Op::ReturnNone => 34,
+        Op::Print => 35,
+        Op::PrintZone => 36,
+        Op::PrintEnd { .. } => 37,
+        Op::Halt => 38,
+        Op::IntDivide => 39,
+    }
+}
+
+fn corrupt(reason: String) -> Error {
+    LoadError::Corrupt { reason }.into()
+}
+
+fn put_u16(bytes: &mut Vec<u8>, value: u16) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_str(bytes: &mut Vec<u8>, text: &str) {
+    put_u32(bytes, text.len() as u32);
+    bytes.extend_from_slice(text.as_bytes());
+}
+
+/// Writes a program in the `.jpoc` format. The line table is only written when
+/// `lines` is set, without it runtime errors can't say which line they come from.
+pub fn encode(program: &Program, lines: bool) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    put_u16(&mut bytes, VERSION);
+    put_u16(&mut bytes, if lines { LINE_TABLE } else { 0 });
+
+    put_u32(&mut bytes, program.constants.len() as u32);
+    for constant in &program.constants {
+        match constant {
+            Value::String(text) => {
+                bytes.push(0);
+                put_str(&mut bytes, text);
+            },
+            Value::Float(float) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&float.to_le_bytes());
+            },
+            Value::Integer(integer) => {
+                bytes.push(3);
+                bytes.extend_from_slice(&integer.to_le_bytes());
+            },
+            Value::Boolean(boolean) => {
+                bytes.push(2);
+                bytes.push(*boolean as u8);
+            },
+        }
+    }
+
+    put_u32(&mut bytes, program.names.len() as u32);
+    for name in &program.names {
+        put_str(&mut bytes, name);
+    }
+
+    put_u16(&mut bytes, program.locals.len() as u16);
+    for &local in &program.locals {
+        put_u16(&mut bytes, local);
+    }
+
+    put_u32(&mut bytes, program.functions.len() as u32);
+    for function in &program.functions {
+        put_u16(&mut bytes, function.name);
+        put_u16(&mut bytes, function.arity);
+        put_u16(&mut bytes, function.locals.len() as u16);
+        for &local in &function.locals {
+            put_u16(&mut bytes, local);
+        }
+        put_u32(&mut bytes, function.start);
+    }
+
+    put_u32(&mut bytes, program.code.len() as u32);
+    for &op in &program.code {
+        bytes.push(opcode(op));
+        match op {
+            Op::Constant(n) | Op::Load(n) | Op::Store(n) | Op::Define(n) | Op::LoadLocal(n)
+                | Op::StoreLocal(n) | Op::DefineLocal(n) | Op::DefineFunction(n) => put_u16(&mut bytes, n),
+            Op::Jump(target) | Op::JumpIfFalse(target) | Op::ForTest(target) | Op::ForStep(target) => put_u32(&mut bytes, target),
+            Op::ForNumber(part) => bytes.push(part as u8),
+            Op::Call { name, argc } | Op::CallDiscard { name, argc } => {
+                put_u16(&mut bytes, name);
+                put_u16(&mut bytes, argc);
+            },
+            Op::PrintEnd { newline } => bytes.push(newline as u8),
+            _ => {},
+        }
+    }
+
+    if lines {
+        let mut runs: Vec<(u32, u32)> = Vec::new();
+        for (idx, origin) in program.origins.iter().enumerate() {
+            let line = origin.at.line as u32;
+            if runs.last().is_none_or(|&(_, last)| last != line) {
+                runs.push((idx as u32, line));
+            }
+        }
+        put_u32(&mut bytes, runs.len() as u32);
+        for (start, line) in runs {
+            put_u32(&mut bytes, start);
+            put_u32(&mut bytes, line);
+        }
+    }
+    bytes
+}
+
+// reads the file front to back, section names which part is being read for errors
+struct Reader<'b> {
+    bytes: &'b [u8],
+    pos: usize,
+    section: &'static str,
+}
+
+impl<'b> Reader<'b> {
+    fn take(&mut self, count: usize) -> Result<&'b [u8], Error> {
+        match self.bytes.get(self.pos..self.pos.saturating_add(count)) {
+            Some(taken) => {
+                self.pos += count;
+                Ok(taken)
+            },
+            None => Err(LoadError::Truncated { section: self.section.to_string() }.into()),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> Result<f64, Error> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn i64(&mut self) -> Result<i64, Error> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let len = self.u32()? as usize;
+        match String::from_utf8(self.take(len)?.to_vec()) {
+            Ok(text) => Ok(text),
+            Err(_) => Err(corrupt(std::format!("A STRING IN THE {} IS NOT VALID UTF-8", self.section))),
+        }
+    }
+
+    fn op(&mut self, idx: usize) -> Result<Op, Error> {
+        let op = match self.u8()? {
+            0 => Op::Constant(self.u16()?),
+            1 => Op::Load(self.u16()?),
+            2 => Op::Store(self.u16()?),
+            3 => Op::Define(self.u16()?),
+            4 => Op::LoadLocal(self.u16()?),
+            5 => Op::StoreLocal(self.u16()?),
+            6 => Op::DefineLocal(self.u16()?),
+            7 => Op::Pop,
+            8 => Op::Not,
+            9 => Op::Negate,
+            10 => Op::Add,
+            11 => Op::Subtract,
+            12 => Op::Multiply,
+            13 => Op::Divide,
+            14 => Op::Modulo,
+            15 => Op::Power,
+            16 => Op::Concat,
+            17 => Op::Equal,
+            18 => Op::NotEqual,
+            19 => Op::Less,
+            20 => Op::LessEqual,
+            21 => Op::Greater,
+            22 => Op::GreaterEqual,
+            23 => Op::And,
+            24 => Op::Or,
+            25 => Op::Jump(self.u32()?),
+            26 => Op::JumpIfFalse(self.u32()?),
+            27 => match self.u8()? {
+                0 => Op::ForNumber(LoopPart::Start),
+                1 => Op::ForNumber(LoopPart::Bound),
+                2 => Op::ForNumber(LoopPart::Step),
+                part => return Err(corrupt(std::format!("UNKNOWN FOR LOOP PART {} AT INSTRUCTION {}", part, idx))),
+            },
+            28 => Op::ForTest(self.u32()?),
+            29 => Op::ForStep(self.u32()?),
+            30 => Op::DefineFunction(self.u16()?),
+            31 => Op::Call { name: self.u16()?, argc: self.u16()? },
+            32 => Op::CallDiscard { name: self.u16()?, argc: self.u16()? },
+            33 => Op::Return,
+            34 => Op::ReturnNone,
+            35 => Op::Print,
+            36 => Op::PrintZone,
+            37 => Op::PrintEnd { newline: self.u8()? != 0 },
+            38 => Op::Halt,
+            39 => Op::IntDivide,
+            other => return Err(corrupt(std::format!("UNKNOWN OPCODE {} AT INSTRUCTION {}", other, idx))),
+        };
+        Ok(op)
+    }
+}
+
+/// Reads a program written by [`encode`], rejecting anything that isn't a complete
+/// and valid compiled program.
+pub fn decode(bytes: &[u8]) -> Result<Program, Error> {
+    let mut reader = Reader { bytes, pos: 0, section: "HEADER" };
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(LoadError::NotCompiled.into());
+    }
+    reader.take(MAGIC.len())?;
+    let version = reader.u16()?;
+    if version != VERSION {
+        return Err(LoadError::UnsupportedVersion { found: version, supported: VERSION }.into());
+    }
+    let flags = reader.u16()?;
+    if flags & !LINE_TABLE != 0 {
+        return Err(corrupt(std::format!("UNKNOWN FLAGS {:#06x}", flags)));
+    }
+
+    let mut program = Program::default();
+    reader.section = "CONSTANT POOL";
+    for _ in 0..reader.u32()? {
+        let constant = match reader.u8()? {
+            0 => Value::String(reader.string()?),
+            1 => match reader.f64()? {
+                // no literal evaluates to NaN
+                float if float.is_nan() => return Err(corrupt("A FLOAT CONSTANT IS NOT A NUMBER".to_string())),
+                float => Value::Float(float),
+            },
+            2 => Value::Boolean(reader.u8()? != 0),
+            3 => Value::Integer(reader.i64()?),
+            tag => return Err(corrupt(std::format!("UNKNOWN CONSTANT TAG {}", tag))),
+        };
+        program.constants.push(constant);
+    }
+
+    reader.section = "NAME TABLE";
+    for _ in 0..reader.u32()? {
+        program.names.push(reader.string()?);
+    }
+
+    reader.section = "LOCALS";
+    for _ in 0..reader.u16()? {
+        program.locals.push(reader.u16()?);
+    }
+
+    reader.section = "FUNCTION TABLE";
+    for _ in 0..reader.u32()? {
+        let name = reader.u16()?;
+        let arity = reader.u16()?;
+        let mut locals = Vec::new();
+        for _ in 0..reader.u16()? {
+            locals.push(reader.u16()?);
+        }
+        let start = reader.u32()?;
+        program.functions.push(Function { name, arity, locals, start });
+    }
+
+    reader.section = "CODE";
+    let count = reader.u32()? as usize;
+    for idx in 0..count {
+        program.code.push(reader.op(idx)?);
+    }
+
+    program.origins = vec![Origin::default(); program.code.len()];
+    if flags & LINE_TABLE != 0 {
+        reader.section = "LINE TABLE";
+        let mut runs = Vec::new();
+        for _ in 0..reader.u32()? {
+            runs.push((reader.u32()? as usize, reader.u32()? as usize));
+        }
+        for (n, &(start, line)) in runs.iter().enumerate() {
+            let end = runs.get(n + 1).map_or(program.code.len(), |&(next, _)| next);
+            if start >= end || end > program.code.len() {
+                return Err(corrupt("THE LINE TABLE DOESN'T MATCH THE CODE".to_string()));
+            }
+            let span = Span { line, ..Span::default() };
+            for origin in &mut program.origins[start..end] {
+                *origin = Origin { at: span, value: span };
+            }
+        }
+    }
+    if reader.pos != bytes.len() {
+        return Err(corrupt(std::format!("{} UNEXPECTED BYTES AT THE END", bytes.len() - reader.pos)));
+    }
+
+    verify(&program)?;
+    Ok(program)
+}
+
+// how many values an instruction needs on the stack and how many it leaves instead
+fn stack_effect(op: Op) -> (usize, usize) {
+    match op {
+        Op::Constant(_) | Op::Load(_) | Op::LoadLocal(_) => (0, 1),
+        Op::Store(_) | Op::Define(_) | Op::StoreLocal(_) | Op::DefineLocal(_) | Op::Pop | Op::Print
+            | Op::JumpIfFalse(_) | Op::Return => (1, 0),
+        Op::Not | Op::Negate | Op::ForNumber(_) => (1, 1),
+        Op::ForTest(_) | Op::ForStep(_) => (3, 3),
+        Op::Call { argc, .. } => (argc as usize, 1),
+        Op::CallDiscard { argc, .. } => (argc as usize, 0),
+        Op::Jump(_) | Op::DefineFunction(_) | Op::ReturnNone | Op::PrintZone | Op::PrintEnd { .. } | Op::Halt => (0, 0),
+        // every binary operator
+        _ => (2, 1),
+    }
+}
+
+// checks that every index points into its table and that no instruction can take more
+// values from the stack than there are, in the top level code and in every function
+fn verify(program: &Program) -> Result<(), Error> {
+    if program.code.is_empty() {
+        return Err(corrupt("THE PROGRAM HAS NO CODE".to_string()));
+    }
+    if program.locals.iter().any(|&local| local as usize >= program.names.len()) {
+        return Err(corrupt("THE TOP LEVEL CODE HAS A BAD LOCAL".to_string()));
+    }
+    let mut previous = 0;
+    for function in &program.functions {
+        let start = function.start as usize;
+        if start <= previous || start >= program.code.len() {
+            return Err(corrupt(std::format!("FUNCTION {} STARTS AT {}, OUTSIDE OF THE CODE", function.name, start)));
+        }
+        previous = start;
+        if function.name as usize >= program.names.len()
+            || function.locals.iter().any(|&local| local as usize >= program.names.len())
+            || function.arity as usize > function.locals.len()
+        {
+            return Err(corrupt(std::format!("FUNCTION AT {} HAS A BAD NAME OR LOCALS", start)));
+        }
+    }
+
+    // the top level code runs up to the first function, each function up to the next
+    let mut regions: Vec<(usize, Option<&Function>)> = vec![(0, None)];
+    regions.extend(program.functions.iter().map(|function| (function.start as usize, Some(function))));
+    for (n, &(start, function)) in regions.iter().enumerate() {
+        let end = regions.get(n + 1).map_or(program.code.len(), |&(next, _)| next);
+        verify_region(program, start, end, function)?;
+    }
+    Ok(())
+}
+
+fn verify_region(program: &Program, start: usize, end: usize, function: Option<&Function>) -> Result<(), Error> {
+    let locals = function.map_or(program.locals.len(), |function| function.locals.len());
+    let mut heights: Vec<Option<usize>> = vec![None; end - start];
+    let mut pending = vec![(start, 0)];
+    while let Some((idx, height)) = pending.pop() {
+        match heights[idx - start] {
+            Some(seen) if seen == height => continue,
+            Some(_) => return Err(corrupt(std::format!("THE STACK HEIGHT AT INSTRUCTION {} DEPENDS ON THE PATH TAKEN", idx))),
+            None => heights[idx - start] = Some(height),
+        }
+
+        let op = program.code[idx];
+        let in_range = match op {
+            Op::Constant(n) => (n as usize) < program.constants.len(),
+            Op::Load(n) | Op::Store(n) | Op::Define(n) => (n as usize) < program.names.len(),
+            Op::Call { name, .. } | Op::CallDiscard { name, .. } => (name as usize) < program.names.len(),
+            Op::LoadLocal(slot) | Op::StoreLocal(slot) | Op::DefineLocal(slot) => (slot as usize) < locals,
+            Op::DefineFunction(n) => (n as usize) < program.functions.len(),
+            Op::Jump(target) | Op::JumpIfFalse(target) | Op::ForTest(target) | Op::ForStep(target) => {
+                (start..end).contains(&(target as usize))
+            },
+            Op::Return | Op::ReturnNone => function.is_some(),
+            _ => true,
+        };
+        if !in_range {
+            return Err(corrupt(std::format!("BAD OPERAND FOR {} AT INSTRUCTION {}", op.mnemonic(), idx)));
+        }
+
+        let (needs, leaves) = stack_effect(op);
+        if needs > height {
+            return Err(corrupt(std::format!("{} AT INSTRUCTION {} NEEDS MORE VALUES THAN THE STACK HOLDS", op.mnemonic(), idx)));
+        }
+        let height = height - needs + leaves;
+        let mut next = |target: usize, height: usize| {
+            if target >= end {
+                return Err(corrupt(std::format!("INSTRUCTION {} RUNS PAST THE END OF ITS CODE", idx)));
+            }
+            pending.push((target, height));
+            Ok(())
+        };
+        match op {
+            Op::Jump(target) => next(target as usize, height)?,
+            Op::JumpIfFalse(target) | Op::ForStep(target) => {
+                next(target as usize, height)?;
+                next(idx + 1, height)?;
+            },
+            // the counter is only pushed when the loop goes on
+            Op::ForTest(target) => {
+                next(target as usize, height)?;
+                next(idx + 1, height + 1)?;
+            },
+            Op::Return | Op::ReturnNone | Op::Halt => {},
+            _ => next(idx + 1, height)?,
+        }
+    }
+    Ok(())
+}
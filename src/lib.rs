@@ -0,0 +1,12 @@
+pub mod error;
+pub mod diagnostic;
+pub mod scanner;
+pub mod value;
+pub mod ast;
+pub mod parser;
+pub mod resolver;
+pub mod executer;
+pub mod bytecode;
+pub mod compiler;
+pub mod vm;
+pub mod jpoc;
@@ -1,40 +1,323 @@
-mod scanner;
-//mod parser;
-mod executer;
+mod repl;
+use basic_compiler::ast::Stmt;
+use basic_compiler::error::Error;
+use basic_compiler::scanner::Dialect;
+use basic_compiler::bytecode::{self, Program};
+use basic_compiler::{compiler, diagnostic, executer, jpoc, parser, resolver, scanner, vm};
+use std::env;
 use std::fs;
+use std::io::{self, ErrorKind, Read, StdoutLock, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::thread;
 
-//fn print_tree(root: &parser::Node, level: u8) {
-  //  println!("{}> {}", "-".repeat(level as usize), root);
-    //for child in &root.children {
-      //  print_tree(&child, level + 1);
-    //}
-//}
-
-            //let ast = parser::parse(&tokens);
-            //match ast {
-              //  Ok(root) => {
-                //        print_tree(&root, 0);
-                //},
-                //Err(message) => {
-                  //  println!("ERROR ON PARSER: {}", message);
-                //}
-fn main() {
-    let contents = fs::read_to_string("src/buzz.jpo")
-        .expect("Should have been able to read the file");
-    
-    let tokens = scanner::scan(&contents);
-    match tokens {
-        Ok(tokens) => {
-            let result = executer::execute(&tokens);
-            match result {
-                Err(message) => {
-                    println!("{}", message);
-                },
-                Ok(_) => {},
+const USAGE: &str = "\
+USAGE:
+    basic-compiler <COMMAND> [OPTIONS] <FILE>
+    basic-compiler [repl] [--case <CASE>]
+
+COMMANDS:
+    repl             start an interactive session (default)
+    run <FILE>       run a program, or a program compiled to a .jpoc file
+    compile <FILE>   compile a program to a .jpoc file
+    check <FILE>     scan, parse and resolve a program without running it
+    tokens <FILE>    print the tokens produced by the scanner
+    ast <FILE>       print the syntax tree
+    disasm <FILE>    print the bytecode a program (or a .jpoc file) compiles to
+
+OPTIONS:
+    --max-depth <N>  maximum depth of FUNCTION calls (default 200)
+    --walk           run the syntax tree directly instead of compiling it to bytecode
+    -o <FILE>        where compile writes to (default FILE with a .jpoc extension)
+    --no-lines       leave the line table out of the .jpoc file, errors won't have a line
+    --case <CASE>    upper: keywords only in uppercase (default)
+                     fold: keywords in any case, names folded to uppercase
+                     keep: keywords in any case, names keep their case
+
+Use '-' as FILE to read the program from stdin.";
+
+const STACK_PER_CALL: usize = 16 * 1024;
+// enough for blocks and expressions nested parser::MAX_NESTING deep
+const NESTING_STACK: usize = parser::MAX_NESTING * 32 * 1024;
+
+struct Options {
+    max_depth: usize,
+    walk: bool,
+    output: Option<String>,
+    lines: bool,
+    dialect: Dialect,
+}
+
+struct Source {
+    name: String,
+    contents: String,
+}
+
+impl Source {
+    fn error(&self, error: &Error) -> String {
+        diagnostic::render(error, &self.name, &self.contents)
+    }
+}
+
+// writes a listing to stdout. a reader that stops early, like `| head`, closes the
+// pipe, and that ends the listing instead of being an error
+fn print(write: impl FnOnce(&mut StdoutLock) -> io::Result<()>) -> Result<(), String> {
+    let mut stdout = io::stdout().lock();
+    match write(&mut stdout).and_then(|_| stdout.flush()) {
+        Err(error) if error.kind() != ErrorKind::BrokenPipe => Err(std::format!("COULD NOT WRITE TO STDOUT: {}", error)),
+        _ => Ok(()),
+    }
+}
+
+// the name errors give a file, and its bytes. '-' reads stdin
+fn read_input(path: &str) -> Result<(String, Vec<u8>), String> {
+    if path == "-" {
+        let mut bytes = Vec::new();
+        match io::stdin().read_to_end(&mut bytes) {
+            Ok(_) => Ok(("<stdin>".to_string(), bytes)),
+            Err(error) => Err(std::format!("COULD NOT READ STDIN: {}", error)),
+        }
+    } else {
+        match fs::read(path) {
+            Ok(bytes) => Ok((path.to_string(), bytes)),
+            Err(error) => Err(std::format!("COULD NOT READ '{}': {}", path, error)),
+        }
+    }
+}
+
+fn read_source(name: String, bytes: Vec<u8>) -> Result<Source, String> {
+    match String::from_utf8(bytes) {
+        Ok(contents) => Ok(Source { name, contents }),
+        Err(error) => Err(std::format!("COULD NOT READ '{}': {}", name, error)),
+    }
+}
+
+fn run(source: &Source, options: &Options) -> Result<(), String> {
+    let tokens = match scanner::scan_with(&source.contents, options.dialect) {
+        Ok(tokens) => tokens,
+        Err(error) => return Err(source.error(&error)),
+    };
+    let program = match parser::parse(&tokens) {
+        Ok(program) => program,
+        Err(error) => return Err(source.error(&error)),
+    };
+    if options.walk {
+        return walk(source, options, &program);
+    }
+    let program = compiler::compile(&program).map_err(|error| source.error(&error))?;
+    let mut vm = vm::Vm::new();
+    vm.set_max_depth(options.max_depth);
+    vm.run(&program).map_err(|error| source.error(&error))
+}
+
+fn walk(source: &Source, options: &Options, program: &[Stmt]) -> Result<(), String> {
+    let mut interpreter = executer::Interpreter::new();
+    interpreter.set_max_depth(options.max_depth);
+    interpreter.execute(program).map_err(|error| source.error(&error))
+}
+
+fn check(source: &Source, options: &Options) -> Result<(), String> {
+    let tokens = match scanner::scan_with(&source.contents, options.dialect) {
+        Ok(tokens) => tokens,
+        Err(error) => return Err(source.error(&error)),
+    };
+    match parser::parse(&tokens).and_then(|mut program| resolver::resolve(&mut program)) {
+        Ok(_) => Ok(()),
+        Err(error) => Err(source.error(&error)),
+    }
+}
+
+fn tokens(source: &Source, options: &Options) -> Result<(), String> {
+    match scanner::scan_with(&source.contents, options.dialect) {
+        Ok(tokens) => print(|stdout| {
+            for token in &tokens {
+                // comments after the last token are kept on it as well
+                let (before, after): (Vec<_>, Vec<_>) = token.trivia
+                    .iter()
+                    .partition(|comment| comment.span.offset < token.span.offset);
+                for comment in before {
+                    writeln!(stdout, "{}", comment)?;
+                }
+                writeln!(stdout, "{}", token)?;
+                for comment in after {
+                    writeln!(stdout, "{}", comment)?;
+                }
+            }
+            Ok(())
+        }),
+        Err(error) => Err(source.error(&error)),
+    }
+}
+
+fn ast(source: &Source, options: &Options) -> Result<(), String> {
+    let tokens = match scanner::scan_with(&source.contents, options.dialect) {
+        Ok(tokens) => tokens,
+        Err(error) => return Err(source.error(&error)),
+    };
+    match parser::parse(&tokens) {
+        Ok(program) => print(|stdout| {
+            for stmt in &program {
+                write!(stdout, "{}", stmt)?;
+            }
+            Ok(())
+        }),
+        Err(error) => Err(source.error(&error)),
+    }
+}
+
+fn compile_source(source: &Source, options: &Options) -> Result<Program, String> {
+    let program = scanner::scan_with(&source.contents, options.dialect)
+        .and_then(|tokens| parser::parse(&tokens))
+        .and_then(|program| compiler::compile(&program));
+    program.map_err(|error| source.error(&error))
+}
+
+fn compile(source: &Source, options: &Options) -> Result<(), String> {
+    let program = compile_source(source, options)?;
+    let output = match &options.output {
+        Some(output) => output.clone(),
+        None if source.name != "<stdin>" => Path::new(&source.name).with_extension("jpoc").display().to_string(),
+        None => return Err("-o IS NEEDED TO COMPILE A PROGRAM FROM STDIN".to_string()),
+    };
+    match fs::write(&output, jpoc::encode(&program, options.lines)) {
+        Ok(_) => Ok(()),
+        Err(error) => Err(std::format!("COULD NOT WRITE '{}': {}", output, error)),
+    }
+}
+
+fn disasm(source: &Source, options: &Options) -> Result<(), String> {
+    let listing = bytecode::disassemble(&compile_source(source, options)?);
+    print(|stdout| write!(stdout, "{}", listing))
+}
+
+// a compiled program has no source, its errors only point at a line
+fn read_compiled(name: &str, bytes: &[u8]) -> Result<Program, String> {
+    jpoc::decode(bytes).map_err(|error| diagnostic::render_compiled(&error, name))
+}
+
+fn run_compiled(name: &str, bytes: &[u8], options: &Options) -> Result<(), String> {
+    if options.walk {
+        return Err(std::format!("--walk NEEDS THE SOURCE OF THE PROGRAM, '{}' IS COMPILED", name));
+    }
+    let program = read_compiled(name, bytes)?;
+    let mut vm = vm::Vm::new();
+    vm.set_max_depth(options.max_depth);
+    vm.run(&program).map_err(|error| diagnostic::render_compiled(&error, name))
+}
+
+fn disasm_compiled(name: &str, bytes: &[u8], _options: &Options) -> Result<(), String> {
+    let listing = bytecode::disassemble(&read_compiled(name, bytes)?);
+    print(|stdout| write!(stdout, "{}", listing))
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut options = Options {
+        max_depth: executer::DEFAULT_MAX_DEPTH,
+        walk: false,
+        output: None,
+        lines: true,
+        dialect: Dialect::default(),
+    };
+    let mut positional: Vec<&str> = Vec::new();
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--max-depth" => match args_iter.next().map(|value| value.parse::<usize>()) {
+                Some(Ok(max_depth)) => options.max_depth = max_depth,
+                _ => {
+                    eprintln!("--max-depth EXPECTS A NUMBER\n\n{}", USAGE);
+                    return ExitCode::from(2);
+                }
+            },
+            "--case" => match args_iter.next().and_then(|name| Dialect::from_name(name)) {
+                Some(dialect) => options.dialect = dialect,
+                None => {
+                    eprintln!("--case EXPECTS upper, fold OR keep\n\n{}", USAGE);
+                    return ExitCode::from(2);
+                }
+            },
+            "--walk" => options.walk = true,
+            "--no-lines" => options.lines = false,
+            "-o" => match args_iter.next() {
+                Some(output) => options.output = Some(output.clone()),
+                None => {
+                    eprintln!("-o EXPECTS A FILE\n\n{}", USAGE);
+                    return ExitCode::from(2);
+                }
+            },
+            _ => positional.push(arg),
+        }
+    }
+    // FUNCTION calls and nested blocks and expressions all recurse through the passes
+    // over the syntax tree, so everything runs on a stack sized for both
+    let stack_size = options.max_depth.saturating_mul(STACK_PER_CALL).saturating_add(NESTING_STACK);
+    thread::scope(|scope| {
+        let handle = thread::Builder::new()
+            .stack_size(stack_size)
+            .spawn_scoped(scope, || dispatch(&positional, &options));
+        match handle {
+            Ok(handle) => handle.join().unwrap_or(ExitCode::FAILURE),
+            Err(error) => {
+                eprintln!("COULD NOT START THE INTERPRETER: {}", error);
+                ExitCode::FAILURE
+            },
+        }
+    })
+}
+
+fn dispatch(positional: &[&str], options: &Options) -> ExitCode {
+    if positional.is_empty() || positional == ["repl"] {
+        return match repl::run(options.dialect) {
+            Ok(_) => ExitCode::SUCCESS,
+            Err(message) => {
+                eprintln!("{}", message);
+                ExitCode::FAILURE
             }
-        },
+        };
+    }
+    if positional.len() != 2 {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    }
+
+    let command: fn(&Source, &Options) -> Result<(), String> = match positional[0] {
+        "run" => run,
+        "compile" => compile,
+        "check" => check,
+        "tokens" => tokens,
+        "ast" => ast,
+        "disasm" => disasm,
+        other => {
+            eprintln!("UNKNOWN COMMAND '{}'\n\n{}", other, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let (name, bytes) = match read_input(positional[1]) {
+        Ok(input) => input,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // run and disasm take a compiled program too, whatever the file is called
+    let result = if bytes.starts_with(jpoc::MAGIC) {
+        match positional[0] {
+            "run" => run_compiled(&name, &bytes, options),
+            "disasm" => disasm_compiled(&name, &bytes, options),
+            other => Err(std::format!("{} NEEDS THE SOURCE OF THE PROGRAM, '{}' IS COMPILED", other.to_uppercase(), name)),
+        }
+    } else {
+        read_source(name, bytes).and_then(|source| command(&source, options))
+    };
+
+    match result {
+        Ok(_) => ExitCode::SUCCESS,
         Err(message) => {
-            println!("ERROR ON SCANNER: {}", message)
+            eprintln!("{}", message);
+            ExitCode::FAILURE
         }
     }
 }
@@ -1,191 +1,598 @@
-use crate::scanner;
-use crate::scanner::{Token, TokenType};
-use std::fmt;
-
-#[derive(Debug)]
-enum Construct {
-    Output,
-    Scope,
-    Operator,
-    Variable,
-    Literal,
-    Branch,
-    Assign,
-}
+//! Turns the tokens of a program into the syntax tree in [`crate::ast`].
+//!
+//! Statements are parsed by recursive descent, one per line, and expressions by
+//! precedence climbing. Block structure is checked here too: every block needs
+//! its `END`, `ELIF`/`ELSE` only belong to an `IF`, `BREAK`/`CONTINUE` to a loop
+//! and `RETURN` to a function.
+use crate::ast::{BinaryOp, Block, Branch, Expr, ExprKind, Ident, PrintItem, Stmt, StmtKind, UnaryOp};
+use crate::error::{Error, ParseError};
+use crate::scanner::{self, Span, Token, TokenType};
+use crate::value::Value;
+
+/// How deep blocks and expressions can nest: every block inside another, and in an
+/// expression parentheses, call arguments, `NOT` and unary `-`, and every operator
+/// of a chain like `1 + 2 + 3`. Every pass over the tree recurses into it, so this
+/// keeps them all within the stack. That takes a few megabytes more than the main
+/// thread gets by default, so the command line runs everything on a larger stack.
+pub const MAX_NESTING: usize = 1000;
 
-pub struct Node {
-    construct: Construct,
-    pub value: Option<String>,
-    pub children: Vec<Node>,
+/// Parses a whole program.
+pub fn parse(tokens: &[Token]) -> Result<Block, Error> {
+    let mut parser = Parser::new(tokens);
+    let mut program = Vec::new();
+    loop {
+        parser.skip_newlines();
+        if parser.peek().is_none() {
+            return Ok(program);
+        }
+        program.push(parser.statement()?);
+    }
 }
 
-impl<'a> fmt::Display for Node {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Node<{:?}, {:?}>", self.construct, self.value)
+/// Parses a single expression, newlines around it are ignored.
+pub fn parse_expression(tokens: &[Token]) -> Result<Expr, Error> {
+    let mut parser = Parser::new(tokens);
+    parser.skip_newlines();
+    let expr = parser.expression()?;
+    parser.skip_newlines();
+    match parser.peek() {
+        Some(_) => Err(parser.expected("AN OPERATOR")),
+        None => Ok(expr),
     }
 }
 
-fn expression_endpoint(tokens: &Vec<Token>, start: usize) -> Option<usize> {
-    let mut idx = start;
-    while tokens[idx].tk_type != TokenType::Newline && idx < tokens.len() {
-        idx += 1;
+/// How tightly a binary operator binds, higher binds tighter. Every level is left
+/// associative. `NOT` binds tighter than all of them, and unary `-` tighter than all
+/// but `^`, so `-2 ^ 2` is `-(2 ^ 2)`.
+pub fn precedence(op: BinaryOp) -> u8 {
+    match op {
+        BinaryOp::Power => 5,
+        BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::IntDivide | BinaryOp::Modulo => 4,
+        BinaryOp::Add | BinaryOp::Subtract => 3,
+        // '&' sits between arithmetic and comparisons, so "N = " & N + 1 adds before joining
+        BinaryOp::Concat => 2,
+        BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::Less | BinaryOp::LessEqual
+            | BinaryOp::Greater | BinaryOp::GreaterEqual => 1,
+        BinaryOp::And | BinaryOp::Or => 0,
     }
-    if idx > start {
-        return Some(idx);
+}
+
+fn binary_op(tk_type: &TokenType) -> Option<BinaryOp> {
+    match tk_type {
+        TokenType::AddOperator => Some(BinaryOp::Add),
+        TokenType::MinusOperator => Some(BinaryOp::Subtract),
+        TokenType::MultOperator => Some(BinaryOp::Multiply),
+        TokenType::DivOperator => Some(BinaryOp::Divide),
+        TokenType::IntDivOperator => Some(BinaryOp::IntDivide),
+        TokenType::ModOperator => Some(BinaryOp::Modulo),
+        TokenType::ExpOperator => Some(BinaryOp::Power),
+        TokenType::ConcatOperator => Some(BinaryOp::Concat),
+        TokenType::EqualsOperator => Some(BinaryOp::Equal),
+        TokenType::NotEqualsOperator => Some(BinaryOp::NotEqual),
+        TokenType::LessThan => Some(BinaryOp::Less),
+        TokenType::LessThanEq => Some(BinaryOp::LessEqual),
+        TokenType::GreaterThan => Some(BinaryOp::Greater),
+        TokenType::GreaterThanEq => Some(BinaryOp::GreaterEqual),
+        TokenType::AndOperator => Some(BinaryOp::And),
+        TokenType::OrOperator => Some(BinaryOp::Or),
+        _ => None,
     }
-    None
 }
 
-fn precedence(operator: &str) -> u8 {
-    match TokenType::token_type(operator).unwrap() {
-        TokenType::NotOperator => 4,
-        TokenType::GreaterThan | TokenType::GreaterThanEq | TokenType::LessThan 
-            | TokenType::LessThanEq | TokenType::EqualsOperator | TokenType::NotEqualsOperator => 3,
-        TokenType::MultOperator | TokenType::DivOperator | TokenType::AndOperator => 2,
-        TokenType::AddOperator | TokenType::MinusOperator | TokenType::OrOperator => 1,
-        _ => 0,
+// the value of a literal token, strings lose their quotes
+fn literal(token: &Token) -> Value {
+    match token.tk_type {
+        TokenType::TrueLiteral => Value::Boolean(true),
+        TokenType::FalseLiteral => Value::Boolean(false),
+        _ if token.lexeme.starts_with('"') => Value::String(scanner::unescape(&token.lexeme)),
+        // the scanner only makes literals of numbers it can read
+        _ => scanner::number(&token.lexeme).unwrap_or(Value::Integer(0)),
     }
 }
 
-fn infix_to_tree(infix: &[Token]) -> Node {
-    // convert to postfix because fu
-    let mut postfix: Vec<&Token> = Vec::new();
-    let mut stack: Vec<&Token> = Vec::new();
-    for op in infix {
-        if op.tk_type == TokenType::Literal || op.tk_type == TokenType::Identifier {
-            postfix.push(op);
-        } else if op.tk_type == TokenType::LParen {
-            stack.push(op);
-        } else if op.tk_type == TokenType::RParen {
-            while stack.len() > 0 && stack.last().unwrap().tk_type != TokenType::LParen {
-                postfix.push(stack.pop().unwrap());
-            }
-            stack.pop();
+struct Parser<'t, 'a> {
+    tokens: &'t [Token<'a>],
+    pos: usize,
+    // loops around the current statement, inside the current function
+    loops: usize,
+    in_function: bool,
+    // levels of blocks and of the expression being parsed, see MAX_NESTING
+    nesting: usize,
+}
+
+impl<'t, 'a> Parser<'t, 'a> {
+    fn new(tokens: &'t [Token<'a>]) -> Parser<'t, 'a> {
+        Parser {
+            tokens,
+            pos: 0,
+            loops: 0,
+            in_function: false,
+            nesting: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<&'t Token<'a>> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_is(&self, tk_type: TokenType) -> bool {
+        self.peek().is_some_and(|token| token.tk_type == tk_type)
+    }
+
+    fn at_line_end(&self) -> bool {
+        self.peek().is_none_or(|token| token.tk_type == TokenType::Newline)
+    }
+
+    fn advance(&mut self) -> &'t Token<'a> {
+        let token = &self.tokens[self.pos];
+        self.pos += 1;
+        token
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek_is(TokenType::Newline) {
+            self.pos += 1;
+        }
+    }
+
+    // the last token consumed, errors about missing tokens point at it
+    fn previous_span(&self) -> Span {
+        match self.pos {
+            0 => self.tokens.first().map_or(Span::default(), |token| token.span),
+            pos => self.tokens[pos - 1].span,
+        }
+    }
+
+    // from start to the end of the last token consumed
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.previous_span())
+    }
+
+    // an error for the next token, or for the end of the line when there is none
+    fn expected(&self, expected: &str) -> Error {
+        let expected = expected.to_string();
+        match self.peek() {
+            Some(found) if found.tk_type != TokenType::Newline => {
+                ParseError::UnexpectedToken { expected, found: found.lexeme.to_string(), span: found.span }.into()
+            },
+            _ => ParseError::MissingToken { expected, span: self.previous_span() }.into(),
+        }
+    }
+
+    fn expect(&mut self, tk_type: TokenType, expected: &str) -> Result<&'t Token<'a>, Error> {
+        if self.peek_is(tk_type) {
+            Ok(self.advance())
         } else {
-            while stack.len() > 0 && precedence(&stack.last().unwrap().lexeme) >= precedence(&op.lexeme) {
-                postfix.push(stack.pop().unwrap());
+            Err(self.expected(expected))
+        }
+    }
+
+    // every statement ends at a newline or at the end of the input
+    fn end_of_line(&mut self) -> Result<(), Error> {
+        match self.peek() {
+            None => Ok(()),
+            Some(token) if token.tk_type == TokenType::Newline => {
+                self.pos += 1;
+                Ok(())
+            },
+            Some(_) => Err(self.expected("END OF LINE")),
+        }
+    }
+
+    // statements up to one of the keywords in until, which is left for the caller.
+    // opener is the keyword that started the block
+    fn block(&mut self, opener: &Token, until: &[TokenType]) -> Result<(Block, &'t Token<'a>), Error> {
+        self.nest(opener)?;
+        let mut body = Vec::new();
+        loop {
+            self.skip_newlines();
+            match self.peek() {
+                None => {
+                    return Err(ParseError::MissingEnd { block: opener.lexeme.to_string(), span: opener.span }.into());
+                },
+                Some(token) if until.contains(&token.tk_type) => {
+                    self.nesting -= 1;
+                    return Ok((body, token));
+                },
+                Some(_) => body.push(self.statement()?),
+            }
+        }
+    }
+
+    fn statement(&mut self) -> Result<Stmt, Error> {
+        let token = self.peek().unwrap();
+        match token.tk_type {
+            TokenType::Initialize => self.let_statement(),
+            TokenType::Identifier if self.tokens.get(self.pos + 1).is_some_and(|next| next.tk_type == TokenType::LParen) => {
+                let expr = self.expression()?;
+                let span = expr.span;
+                self.end_of_line()?;
+                Ok(Stmt { kind: StmtKind::Expr(expr), span })
+            },
+            TokenType::Identifier => self.assign_statement(),
+            TokenType::Output => self.print_statement(),
+            TokenType::If => self.if_statement(),
+            TokenType::ForLoop => self.for_statement(),
+            TokenType::WhileLoop => self.while_statement(),
+            TokenType::Function => self.function_statement(),
+            TokenType::Global => self.global_statement(),
+            TokenType::ReturnValue => {
+                self.advance();
+                if !self.in_function {
+                    return Err(ParseError::ReturnOutsideFunction { span: token.span }.into());
+                }
+                let value = if self.at_line_end() { None } else { Some(self.expression()?) };
+                let span = self.span_from(token.span);
+                self.end_of_line()?;
+                Ok(Stmt { kind: StmtKind::Return(value), span })
+            },
+            TokenType::Break | TokenType::Continue => {
+                self.advance();
+                if self.loops == 0 {
+                    return Err(ParseError::OutsideLoop { keyword: token.lexeme.to_string(), span: token.span }.into());
+                }
+                self.end_of_line()?;
+                let kind = if token.tk_type == TokenType::Break { StmtKind::Break } else { StmtKind::Continue };
+                Ok(Stmt { kind, span: token.span })
+            },
+            TokenType::EndScope => Err(ParseError::UnmatchedEnd { span: token.span }.into()),
+            TokenType::ElseIf | TokenType::Else => {
+                Err(ParseError::MisplacedBranch { keyword: token.lexeme.to_string(), span: token.span }.into())
+            },
+            _ => Err(self.expected("A STATEMENT")),
+        }
+    }
+
+    // the expression after 'BE'
+    fn assigned_value(&mut self) -> Result<Expr, Error> {
+        let assign = self.expect(TokenType::Assign, "ASSIGN OPERATOR")?;
+        if self.at_line_end() {
+            let expected = "VALUE AFTER 'BE'".to_string();
+            return Err(ParseError::MissingToken { expected, span: assign.span }.into());
+        }
+        self.expression()
+    }
+
+    // LET <name> BE <value>
+    fn let_statement(&mut self) -> Result<Stmt, Error> {
+        let start = self.advance().span;
+        let name = self.expect(TokenType::Identifier, "IDENTIFIER SYMBOL")?;
+        let value = self.assigned_value()?;
+        let span = self.span_from(start);
+        self.end_of_line()?;
+        let name = Ident::new(&name.lexeme, name.span);
+        Ok(Stmt { kind: StmtKind::Let { name, value }, span })
+    }
+
+    // <name> BE <value>
+    fn assign_statement(&mut self) -> Result<Stmt, Error> {
+        let name = self.advance();
+        let value = self.assigned_value()?;
+        let span = self.span_from(name.span);
+        self.end_of_line()?;
+        let name = Ident::new(&name.lexeme, name.span);
+        Ok(Stmt { kind: StmtKind::Assign { name, value }, span })
+    }
+
+    // GLOBAL <name> [, <name>] ...
+    fn global_statement(&mut self) -> Result<Stmt, Error> {
+        let start = self.advance().span;
+        let mut names = Vec::new();
+        loop {
+            let name = self.expect(TokenType::Identifier, "VARIABLE NAME AFTER 'GLOBAL'")?;
+            names.push(Ident::new(&name.lexeme, name.span));
+            if !self.peek_is(TokenType::Separator) {
+                break;
+            }
+            self.advance();
+        }
+        let span = self.span_from(start);
+        self.end_of_line()?;
+        Ok(Stmt { kind: StmtKind::Global(names), span })
+    }
+
+    // PRINT <item> [, <item>] [; <item>] ...
+    fn print_statement(&mut self) -> Result<Stmt, Error> {
+        let start = self.advance().span;
+        let mut items = Vec::new();
+        while !self.at_line_end() {
+            match self.peek().unwrap().tk_type {
+                TokenType::Separator => {
+                    self.advance();
+                    items.push(PrintItem::Zone);
+                },
+                TokenType::Semicolon => {
+                    self.advance();
+                    items.push(PrintItem::Join);
+                },
+                _ if matches!(items.last(), Some(PrintItem::Expr(_))) => return Err(self.expected("',' OR ';'")),
+                _ => items.push(PrintItem::Expr(self.expression()?)),
+            }
+        }
+        let span = self.span_from(start);
+        self.end_of_line()?;
+        Ok(Stmt { kind: StmtKind::Print(items), span })
+    }
+
+    // IF <condition> ... [ELIF <condition> ...] [ELSE ...] END
+    fn if_statement(&mut self) -> Result<Stmt, Error> {
+        let opener = self.advance();
+        let until = [TokenType::ElseIf, TokenType::Else, TokenType::EndScope];
+        let mut branches = Vec::new();
+        let mut otherwise = None;
+        loop {
+            let condition = self.expression()?;
+            self.end_of_line()?;
+            let (body, next) = self.block(opener, &until)?;
+            branches.push(Branch { condition, body });
+            self.advance();
+            match next.tk_type {
+                TokenType::ElseIf => continue,
+                TokenType::Else => {
+                    self.end_of_line()?;
+                    let (body, next) = self.block(opener, &until)?;
+                    if next.tk_type != TokenType::EndScope {
+                        return Err(ParseError::BranchAfterElse { keyword: next.lexeme.to_string(), span: next.span }.into());
+                    }
+                    self.advance();
+                    otherwise = Some(body);
+                    break;
+                },
+                _ => break,
             }
-            stack.push(op);
         }
+        let span = self.span_from(opener.span);
+        self.end_of_line()?;
+        Ok(Stmt { kind: StmtKind::If { branches, otherwise }, span })
     }
-    while stack.len() > 0 {
-        postfix.push(stack.pop().unwrap());
+
+    // the body of a loop, BREAK and CONTINUE are allowed inside it
+    fn loop_body(&mut self, opener: &Token) -> Result<Block, Error> {
+        self.loops += 1;
+        let body = self.block(opener, &[TokenType::EndScope]);
+        self.loops -= 1;
+        let (body, _) = body?;
+        self.advance();
+        Ok(body)
+    }
+
+    // FOR <variable> IN <start> TO <end> [STEP <step>]
+    fn for_statement(&mut self) -> Result<Stmt, Error> {
+        let opener = self.advance();
+        let variable = self.expect(TokenType::Identifier, "LOOP VARIABLE AFTER 'FOR'")?;
+        self.expect(TokenType::Operator, "'IN' AFTER LOOP VARIABLE")?;
+        let start = self.expression()?;
+        self.expect(TokenType::ToOperator, "'TO' IN FOR LOOP")?;
+        let end = self.expression()?;
+        let step = if self.peek_is(TokenType::StepOperator) {
+            self.advance();
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.end_of_line()?;
+        let body = self.loop_body(opener)?;
+        let span = self.span_from(opener.span);
+        self.end_of_line()?;
+        let variable = Ident::new(&variable.lexeme, variable.span);
+        Ok(Stmt { kind: StmtKind::For { variable, start, end, step, body }, span })
+    }
+
+    // WHILE <condition>
+    fn while_statement(&mut self) -> Result<Stmt, Error> {
+        let opener = self.advance();
+        if self.at_line_end() {
+            let expected = "CONDITION AFTER 'WHILE'".to_string();
+            return Err(ParseError::MissingToken { expected, span: opener.span }.into());
+        }
+        let condition = self.expression()?;
+        self.end_of_line()?;
+        let body = self.loop_body(opener)?;
+        let span = self.span_from(opener.span);
+        self.end_of_line()?;
+        Ok(Stmt { kind: StmtKind::While { condition, body }, span })
     }
 
-    let mut root = Node {
-        construct: Construct::Operator, // root should be an operator
-        value: None,
-        children: Vec::new(),
-    };
-    let parent = &mut root;
-    for t in &postfix {
-        //println!("D: {}", t);
-        if t.tk_type == TokenType::Literal || t.tk_type == TokenType::Identifier {
-            stack.push(&t);
+    // FUNCTION <name>(<param>, <param>, ...)
+    fn function_statement(&mut self) -> Result<Stmt, Error> {
+        let opener = self.advance();
+        let name = self.expect(TokenType::Identifier, "FUNCTION NAME AFTER 'FUNCTION'")?;
+        let function = name.lexeme.to_string();
+        let open = self.expect(TokenType::LParen, "'(' AFTER FUNCTION NAME")?;
+
+        let mut params: Vec<Ident> = Vec::new();
+        if self.peek_is(TokenType::RParen) {
+            self.advance();
         } else {
-            let op2 = Node {
-                construct: Construct::Literal,
-                value: Some(stack.pop().unwrap().lexeme.to_string()),
-                children: Vec::new(),
+            loop {
+                let param = match self.peek() {
+                    Some(param) if param.tk_type == TokenType::Identifier => self.advance(),
+                    Some(param) if param.tk_type != TokenType::Newline => {
+                        return Err(ParseError::InvalidParameter { function, span: param.span }.into());
+                    },
+                    _ => return Err(ParseError::InvalidParameter { function, span: open.span }.into()),
+                };
+                if params.iter().any(|other| other.name == param.lexeme) {
+                    let name = param.lexeme.to_string();
+                    return Err(ParseError::DuplicateParameter { name, function, span: param.span }.into());
+                }
+                params.push(Ident::new(&param.lexeme, param.span));
+                match self.peek() {
+                    Some(next) if next.tk_type == TokenType::Separator => {
+                        self.advance();
+                    },
+                    Some(next) if next.tk_type == TokenType::RParen => {
+                        self.advance();
+                        break;
+                    },
+                    Some(next) if next.tk_type != TokenType::Newline => {
+                        return Err(ParseError::InvalidParameter { function, span: next.span }.into());
+                    },
+                    _ => {
+                        let expected = std::format!("')' AT THE END OF FUNCTION '{}'", function);
+                        return Err(ParseError::MissingToken { expected, span: self.previous_span() }.into());
+                    },
+                }
+            }
+        }
+        self.end_of_line()?;
+
+        // loops around the definition don't reach into the body
+        let outer = (self.loops, self.in_function);
+        self.loops = 0;
+        self.in_function = true;
+        let body = self.block(opener, &[TokenType::EndScope]);
+        (self.loops, self.in_function) = outer;
+        let (body, _) = body?;
+        self.advance();
+
+        let span = self.span_from(opener.span);
+        self.end_of_line()?;
+        let name = Ident::new(&function, name.span);
+        Ok(Stmt { kind: StmtKind::Function { name, params, body, locals: Vec::new() }, span })
+    }
+
+    fn expression(&mut self) -> Result<Expr, Error> {
+        self.binary(0)
+    }
+
+    fn starts_expression(&self) -> bool {
+        self.peek().is_some_and(|token| matches!(token.tk_type,
+            TokenType::Literal | TokenType::TrueLiteral | TokenType::FalseLiteral | TokenType::Identifier
+                | TokenType::LParen | TokenType::NotOperator | TokenType::NegateOperator))
+    }
+
+    // the operand after an operator, or an error naming the operator when it is missing
+    fn operand(&mut self, operator: &Token, min: Option<u8>) -> Result<Expr, Error> {
+        if !self.starts_expression() {
+            let span = match self.peek() {
+                Some(token) if token.tk_type != TokenType::Newline => token.span,
+                _ => operator.span,
             };
-            let op1 = Node {
-                construct: Construct::Literal,
-                value: Some(stack.pop().unwrap().lexeme.to_string()),
-                children: Vec::new(),
+            return Err(ParseError::MissingOperand { operator: operator.lexeme.to_string(), span }.into());
+        }
+        match min {
+            Some(min) => self.binary(min),
+            None => self.unary(),
+        }
+    }
+
+    // one more level of nesting at token, or an error past MAX_NESTING
+    fn nest(&mut self, token: &Token) -> Result<(), Error> {
+        if self.nesting == MAX_NESTING {
+            return Err(ParseError::TooDeep { max_nesting: MAX_NESTING, span: token.span }.into());
+        }
+        self.nesting += 1;
+        Ok(())
+    }
+
+    // operators binding at least as tight as min. every operator of a chain nests
+    // the ones before it one level deeper, and stays counted until the chain ends
+    fn binary(&mut self, min: u8) -> Result<Expr, Error> {
+        let nesting = self.nesting;
+        let mut left = self.unary()?;
+        while let Some(op) = self.peek().and_then(|token| binary_op(&token.tk_type)) {
+            if precedence(op) < min {
+                break;
+            }
+            let operator = self.advance();
+            self.nest(operator)?;
+            let right = self.operand(operator, Some(precedence(op) + 1))?;
+            left = Expr {
+                span: left.span.to(right.span),
+                kind: ExprKind::Binary { op, operator: operator.span, left: Box::new(left), right: Box::new(right) },
             };
-            parent.value = Some(t.lexeme.to_string());
-            parent.children.push(op1);
-            parent.children.push(op2);
         }
+        self.nesting = nesting;
+        Ok(left)
     }
-    return root;
-}
 
-pub fn parse(tokens: &Vec<Token>) -> Result<Node, String> {
-    let mut root = Node {
-        construct: Construct::Scope,
-        value: None,
-        children: Vec::new(),
-    };
-    let mut idx: usize = 0;
-    let mut scope_stack: Vec<&mut Node> = Vec::new();
-    scope_stack.push(&mut root);
-    while idx < tokens.len() {
-        match tokens[idx].tk_type {
-            TokenType::Initialize => {
-                idx += 1;
-                if tokens[idx].tk_type == TokenType::Identifier {
-                    let tk_var = &tokens[idx];
-                    idx += 1;
-                    if tokens[idx].tk_type == TokenType::Assign {
-                        idx += 1;
-                        let mut assign = Node {
-                            construct: Construct::Assign,
-                            value: None,
-                            children: Vec::new(),
-                        };
-                        let var = Node {
-                            construct: Construct::Variable,
-                            value: Some(tk_var.lexeme.to_string()),
-                            children: Vec::new(),
-                        };
-                        let end = expression_endpoint(tokens, idx).unwrap();
-                        let expression_tokens = &tokens[idx..end];
-                        let expression = infix_to_tree(expression_tokens);
-                        assign.children.push(var);
-                        assign.children.push(expression);
-                        scope_stack.last_mut().unwrap().children.push(assign);
-                    } else {
-                        let error_message = std::format!("EXPECTED ASSIGN OPERATOR, FOUND '{}'", tokens[idx].lexeme);
-                        return Err(error_message);
-                    }
-                } else {
-                    let error_message = std::format!("EXPECTED IDENTIFIER SYMBOL, FOUND '{}'", tokens[idx].lexeme);
-                    return Err(error_message);
-                }
+    fn unary(&mut self) -> Result<Expr, Error> {
+        let op = match self.peek().map(|token| &token.tk_type) {
+            Some(TokenType::NotOperator) => UnaryOp::Not,
+            Some(TokenType::NegateOperator) => UnaryOp::Negate,
+            _ => return self.primary(),
+        };
+        let operator = self.advance();
+        self.nest(operator)?;
+        // the operand of '-' takes a '^' with it, the way it does in arithmetic
+        let min = match op {
+            UnaryOp::Not => None,
+            UnaryOp::Negate => Some(precedence(BinaryOp::Power)),
+        };
+        let operand = self.operand(operator, min)?;
+        self.nesting -= 1;
+        Ok(Expr {
+            span: operator.span.to(operand.span),
+            kind: ExprKind::Unary { op, operator: operator.span, operand: Box::new(operand) },
+        })
+    }
+
+    fn primary(&mut self) -> Result<Expr, Error> {
+        let Some(token) = self.peek() else {
+            return Err(self.expected("AN EXPRESSION"));
+        };
+        match token.tk_type {
+            TokenType::Literal | TokenType::TrueLiteral | TokenType::FalseLiteral => {
+                self.advance();
+                Ok(Expr { kind: ExprKind::Literal(literal(token)), span: token.span })
             },
-            TokenType::Output => {
-                idx += 1;
-                let mut output = Node {
-                    construct: Construct::Output,
-                    value: None,
-                    children: Vec::new(),
-                };
-                match tokens[idx].tk_type {
-                    TokenType::Identifier => {
-                        let expression = Node {
-                            construct: Construct::Variable,
-                            value: Some(tokens[idx].lexeme.to_string()),
-                            children: Vec::new(),
-                        };
-                        output.children.push(expression);
-                    },
-                    _ => {},
+            TokenType::Identifier if self.tokens.get(self.pos + 1).is_some_and(|next| next.tk_type == TokenType::LParen) => {
+                self.call()
+            },
+            TokenType::Identifier => {
+                self.advance();
+                Ok(Expr { kind: ExprKind::Variable(Ident::new(&token.lexeme, token.span)), span: token.span })
+            },
+            TokenType::LParen => {
+                self.advance();
+                self.nest(token)?;
+                let inner = self.expression()?;
+                self.nesting -= 1;
+                if self.at_line_end() {
+                    return Err(ParseError::UnclosedParen { span: token.span }.into());
                 }
-                scope_stack.last_mut().unwrap().children.push(output);
+                let close = self.expect(TokenType::RParen, "')'")?;
+                Ok(Expr { kind: ExprKind::Grouping(Box::new(inner)), span: token.span.to(close.span) })
             },
-            TokenType::If => {
-                idx += 1;
-                let mut branch = Node {
-                    construct: Construct::Branch,
-                    value: None,
-                    children: Vec::new(),
-                };
-                let end = expression_endpoint(tokens, idx).unwrap();
-                let condition_tokens = &tokens[idx..end];
-                let tree = infix_to_tree(condition_tokens);
-                branch.children.push(tree);
-                scope_stack.last_mut().unwrap().children.push(branch);
+            TokenType::RParen => Err(ParseError::UnmatchedParen { span: token.span }.into()),
+            _ if binary_op(&token.tk_type).is_some() => {
+                Err(ParseError::MissingOperand { operator: token.lexeme.to_string(), span: token.span }.into())
             },
-            //TokenType::EndScope => {
-              //  if scope_stack.len() == 1 {
-                //    return Err("EXTRA END SCOPE KEYWORD FOUND".to_string());
-                //}
-                //scope_stack.pop();
-            //}
-            _ => {},
+            _ => Err(self.expected("AN EXPRESSION")),
+        }
+    }
+
+    // <name>(<argument>, <argument>, ...)
+    fn call(&mut self) -> Result<Expr, Error> {
+        let name = self.advance();
+        let open = self.advance();
+        self.nest(open)?;
+        let mut args = Vec::new();
+        if !self.peek_is(TokenType::RParen) {
+            loop {
+                if self.at_line_end() {
+                    return Err(ParseError::UnclosedParen { span: open.span }.into());
+                }
+                if self.peek_is(TokenType::Separator) || self.peek_is(TokenType::RParen) {
+                    let expected = std::format!("ARGUMENT IN CALL TO '{}'", name.lexeme);
+                    return Err(ParseError::MissingToken { expected, span: name.span }.into());
+                }
+                args.push(self.expression()?);
+                if self.at_line_end() {
+                    return Err(ParseError::UnclosedParen { span: open.span }.into());
+                }
+                if !self.peek_is(TokenType::Separator) {
+                    break;
+                }
+                self.advance();
+            }
         }
-        idx += 1;
+        let close = self.expect(TokenType::RParen, "',' OR ')'")?;
+        self.nesting -= 1;
+        Ok(Expr {
+            kind: ExprKind::Call { name: Ident::new(&name.lexeme, name.span), args },
+            span: name.span.to(close.span),
+        })
     }
-    Ok(root)
 }
@@ -0,0 +1,151 @@
+use basic_compiler::error::{Error, ScanError};
+use basic_compiler::executer::Interpreter;
+use basic_compiler::{diagnostic, parser};
+use basic_compiler::scanner::{self, Dialect, Token, TokenType};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::fs;
+
+const PROMPT: &str = "> ";
+const CONTINUE_PROMPT: &str = "... ";
+// file name shown in error messages
+const REPL_NAME: &str = "<repl>";
+
+const HELP: &str = "\
+:vars          list every variable and its value
+:reset         forget every variable and function
+:load <FILE>   run a file inside the current session
+:help          show this message
+:quit          leave the REPL";
+
+// how many blocks (IF, FOR, WHILE, FUNCTION) are still waiting for their END
+fn open_blocks(tokens: &[Token]) -> i32 {
+    let mut depth = 0;
+    for token in tokens {
+        match token.tk_type {
+            TokenType::If | TokenType::ForLoop | TokenType::WhileLoop | TokenType::Function => depth += 1,
+            TokenType::EndScope => depth -= 1,
+            _ => {},
+        }
+    }
+    depth
+}
+
+// anything that doesn't start like a statement gets evaluated and echoed
+fn is_statement(tokens: &[Token]) -> bool {
+    let mut statement = tokens.iter().filter(|token| token.tk_type != TokenType::Newline);
+    match statement.next() {
+        Some(token) => match token.tk_type {
+            TokenType::Identifier => {
+                matches!(statement.next(), Some(next) if next.tk_type == TokenType::Assign)
+            },
+            TokenType::Initialize | TokenType::Output | TokenType::If | TokenType::Else
+                | TokenType::ElseIf | TokenType::ForLoop | TokenType::WhileLoop
+                | TokenType::Function | TokenType::Global | TokenType::ReturnValue | TokenType::EndScope => true,
+            _ => false,
+        },
+        None => true,
+    }
+}
+
+fn load(interpreter: &mut Interpreter, dialect: Dialect, path: &str) -> Result<(), String> {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(error) => return Err(std::format!("COULD NOT READ '{}': {}", path, error)),
+    };
+    let result = scanner::scan_with(&source, dialect)
+        .and_then(|tokens| parser::parse(&tokens))
+        .and_then(|program| interpreter.execute(&program));
+    result.map_err(|error| diagnostic::render(&error, path, &source))
+}
+
+// returns false when the REPL should stop
+fn meta_command(interpreter: &mut Interpreter, dialect: Dialect, line: &str) -> bool {
+    let mut words = line.split_whitespace();
+    match words.next() {
+        Some(":vars") => {
+            for (name, value) in interpreter.variables() {
+                println!("{} = {}", name, value);
+            }
+        },
+        Some(":reset") => {
+            interpreter.reset();
+        },
+        Some(":load") => match words.next() {
+            Some(path) => {
+                if let Err(message) = load(interpreter, dialect, path) {
+                    eprintln!("{}", message);
+                }
+            },
+            None => eprintln!("USAGE: :load <FILE>"),
+        },
+        Some(":help") => println!("{}", HELP),
+        Some(":quit") | Some(":q") => return false,
+        Some(other) => eprintln!("UNKNOWN COMMAND '{}', TRY :help", other),
+        None => {},
+    }
+    true
+}
+
+fn evaluate(interpreter: &mut Interpreter, dialect: Dialect, source: &str) -> Result<(), Error> {
+    let tokens = scanner::scan_with(source, dialect)?;
+    if is_statement(&tokens) {
+        interpreter.execute(&parser::parse(&tokens)?)
+    } else {
+        if let Some(value) = interpreter.evaluate(&parser::parse_expression(&tokens)?)? {
+            println!("{}", value);
+        }
+        Ok(())
+    }
+}
+
+pub fn run(dialect: Dialect) -> Result<(), String> {
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(error) => return Err(std::format!("COULD NOT START THE REPL: {}", error)),
+    };
+    let mut interpreter = Interpreter::new();
+    // lines of a block that hasn't reached its END yet
+    let mut buffer = String::new();
+
+    println!("Type :help for a list of commands.");
+    loop {
+        let prompt = if buffer.is_empty() { PROMPT } else { CONTINUE_PROMPT };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                // ctrl-c drops an unfinished block
+                buffer.clear();
+                continue;
+            },
+            Err(ReadlineError::Eof) => break,
+            Err(error) => return Err(std::format!("COULD NOT READ INPUT: {}", error)),
+        };
+        if !line.trim().is_empty() {
+            let _ = editor.add_history_entry(line.as_str());
+        }
+
+        if buffer.is_empty() && line.trim_start().starts_with(':') {
+            if !meta_command(&mut interpreter, dialect, line.trim()) {
+                break;
+            }
+            continue;
+        }
+
+        buffer.push_str(&line);
+        buffer.push('\n');
+        let waiting = match scanner::scan_with(&buffer, dialect) {
+            Ok(tokens) => open_blocks(&tokens) > 0,
+            // a block comment can go on over the next lines, a string can't
+            Err(Error::Scan(ScanError::UnterminatedComment { .. })) => true,
+            Err(_) => false,
+        };
+        if !waiting {
+            if let Err(error) = evaluate(&mut interpreter, dialect, &buffer) {
+                eprintln!("{}", diagnostic::render(&error, REPL_NAME, &buffer));
+            }
+            buffer.clear();
+        }
+    }
+    Ok(())
+}
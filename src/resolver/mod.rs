@@ -0,0 +1,293 @@
+//! Gives every name in a syntax tree the [`Slot`] it is stored in at runtime.
+//!
+//! Names are scoped by block. `LET` at the top level of a program makes a global,
+//! `LET` inside an IF branch, a loop or a FUNCTION body makes a local that only the
+//! rest of that block sees, hiding any outer variable with the same name. FOR
+//! variables belong to their loop and parameters to their function. A name that
+//! isn't local refers to the global, which may be given its value anywhere at the
+//! top level, even after the use. `GLOBAL` makes a name refer to the global for the
+//! rest of its block. Function bodies don't see the locals around their definition,
+//! and function names index a table of their own.
+//!
+//! A variable that can't refer to anything is reported here, before anything runs.
+use crate::ast::{Expr, ExprKind, Ident, PrintItem, Slot, Stmt, StmtKind};
+use crate::error::{Error, ResolveError};
+use std::collections::HashMap;
+
+/// Resolves a whole program on its own. Gives back the names of the local slots
+/// the top level code needs for the variables of its blocks.
+pub fn resolve(program: &mut [Stmt]) -> Result<Vec<String>, Error> {
+    Resolver::new().resolve(program)
+}
+
+// the names a program makes global, with LET at its top level or GLOBAL anywhere
+fn globals(block: &[Stmt], top: bool, names: &mut Vec<String>) {
+    let add = |name: &Ident, names: &mut Vec<String>| {
+        if !names.contains(&name.name) {
+            names.push(name.name.clone());
+        }
+    };
+    for stmt in block {
+        match &stmt.kind {
+            StmtKind::Let { name, .. } if top => add(name, names),
+            StmtKind::Global(global) => {
+                for name in global {
+                    add(name, names);
+                }
+            },
+            StmtKind::If { branches, otherwise } => {
+                for branch in branches {
+                    globals(&branch.body, false, names);
+                }
+                if let Some(body) = otherwise {
+                    globals(body, false, names);
+                }
+            },
+            StmtKind::For { body, .. } | StmtKind::While { body, .. } | StmtKind::Function { body, .. } => {
+                globals(body, false, names);
+            },
+            _ => {},
+        }
+    }
+}
+
+// names where the position of a name is its slot
+#[derive(Default, Clone)]
+struct Table {
+    names: Vec<String>,
+    slots: HashMap<String, usize>,
+}
+
+impl Table {
+    fn slot(&mut self, name: &str) -> usize {
+        if let Some(&slot) = self.slots.get(name) {
+            return slot;
+        }
+        self.names.push(name.to_string());
+        self.slots.insert(name.to_string(), self.names.len() - 1);
+        self.names.len() - 1
+    }
+}
+
+// the blocks of the function, or of the top level code, being resolved
+#[derive(Default, Clone)]
+struct Frame {
+    // the names declared in each block, innermost block last, empty at the top
+    // level of a program
+    scopes: Vec<Vec<(String, Slot)>>,
+    // the name of every local slot, each declaration gets a slot of its own
+    names: Vec<String>,
+}
+
+/// Keeps the global and function tables between programs, so the REPL can resolve
+/// every input against the names of the ones before it. An input that fails to
+/// resolve leaves the tables as they were.
+#[derive(Default, Clone)]
+pub struct Resolver {
+    globals: Table,
+    // whether each global is given a value somewhere, slots are also handed out to
+    // names only used so far
+    defined: Vec<bool>,
+    functions: Table,
+    frame: Frame,
+}
+
+impl Resolver {
+    pub fn new() -> Resolver {
+        Resolver::default()
+    }
+
+    /// Names of the global variables, the position of a name is its slot.
+    pub fn globals(&self) -> &[String] {
+        &self.globals.names
+    }
+
+    /// How many function names have a slot.
+    pub fn functions(&self) -> usize {
+        self.functions.names.len()
+    }
+
+    /// Resolves a program run at the top level. Gives back the names of the local
+    /// slots its top level code needs for the variables of its blocks.
+    pub fn resolve(&mut self, program: &mut [Stmt]) -> Result<Vec<String>, Error> {
+        self.attempt(|resolver| {
+            let mut names = Vec::new();
+            globals(program, true, &mut names);
+            for name in &names {
+                let slot = resolver.global(name);
+                resolver.defined[slot] = true;
+            }
+            resolver.block(program)?;
+            Ok(std::mem::take(&mut resolver.frame.names))
+        })
+    }
+
+    /// Resolves an expression evaluated at the top level.
+    pub fn resolve_expression(&mut self, expr: &mut Expr) -> Result<(), Error> {
+        self.attempt(|resolver| resolver.expression(expr))
+    }
+
+    // resolves on a copy of the tables and only keeps it when resolving succeeds, so a
+    // global assigned by a failed input doesn't count as defined afterwards
+    fn attempt<T>(&mut self, resolve: impl FnOnce(&mut Resolver) -> Result<T, Error>) -> Result<T, Error> {
+        let mut scratch = self.clone();
+        scratch.frame = Frame::default();
+        let resolved = resolve(&mut scratch)?;
+        *self = scratch;
+        Ok(resolved)
+    }
+
+    fn global(&mut self, name: &str) -> usize {
+        let slot = self.globals.slot(name);
+        if slot == self.defined.len() {
+            self.defined.push(false);
+        }
+        slot
+    }
+
+    // the innermost declaration of a variable that is read or assigned
+    fn variable(&self, name: &mut Ident) -> Result<(), Error> {
+        let declared = self.frame.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.iter().rev().find(|(other, _)| *other == name.name));
+        name.slot = match (declared, self.globals.slots.get(&name.name)) {
+            (Some(&(_, slot)), _) => slot,
+            (None, Some(&slot)) if self.defined[slot] => Slot::Global(slot),
+            _ => return Err(ResolveError::UndefinedVariable { name: name.name.clone(), span: name.span }.into()),
+        };
+        Ok(())
+    }
+
+    // LET gives a name a new local slot, unless the block already declared it
+    fn declare(&mut self, name: &mut Ident) {
+        let Some(scope) = self.frame.scopes.last() else {
+            name.slot = Slot::Global(self.global(&name.name));
+            return;
+        };
+        match scope.iter().rev().find(|(other, _)| *other == name.name) {
+            Some(&(_, slot)) => name.slot = slot,
+            None => self.local(name),
+        }
+    }
+
+    // a new local slot in the innermost block
+    fn local(&mut self, name: &mut Ident) {
+        let frame = &mut self.frame;
+        name.slot = Slot::Local(frame.names.len());
+        frame.names.push(name.name.clone());
+        frame.scopes.last_mut().unwrap().push((name.name.clone(), name.slot));
+    }
+
+    fn open(&mut self) {
+        self.frame.scopes.push(Vec::new());
+    }
+
+    fn close(&mut self) {
+        self.frame.scopes.pop();
+    }
+
+    // a block with a scope of its own
+    fn scoped(&mut self, block: &mut [Stmt]) -> Result<(), Error> {
+        self.open();
+        let resolved = self.block(block);
+        self.close();
+        resolved
+    }
+
+    fn block(&mut self, block: &mut [Stmt]) -> Result<(), Error> {
+        for stmt in block {
+            self.statement(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &mut Stmt) -> Result<(), Error> {
+        match &mut stmt.kind {
+            // the value is resolved first, LET X BE X + 1 reads the outer X
+            StmtKind::Let { name, value } => {
+                self.expression(value)?;
+                self.declare(name);
+            },
+            StmtKind::Assign { name, value } => {
+                self.expression(value)?;
+                self.variable(name)?;
+            },
+            StmtKind::Print(items) => {
+                for item in items {
+                    if let PrintItem::Expr(expr) = item {
+                        self.expression(expr)?;
+                    }
+                }
+            },
+            StmtKind::If { branches, otherwise } => {
+                for branch in branches {
+                    self.expression(&mut branch.condition)?;
+                    self.scoped(&mut branch.body)?;
+                }
+                if let Some(body) = otherwise {
+                    self.scoped(body)?;
+                }
+            },
+            StmtKind::For { variable, start, end, step, body } => {
+                self.expression(start)?;
+                self.expression(end)?;
+                if let Some(step) = step {
+                    self.expression(step)?;
+                }
+                self.open();
+                self.local(variable);
+                let resolved = self.block(body);
+                self.close();
+                resolved?;
+            },
+            StmtKind::While { condition, body } => {
+                self.expression(condition)?;
+                self.scoped(body)?;
+            },
+            StmtKind::Function { name, params, body, locals } => {
+                name.slot = Slot::Global(self.functions.slot(&name.name));
+                let outer = std::mem::take(&mut self.frame);
+                self.open();
+                for param in params.iter_mut() {
+                    self.local(param);
+                }
+                let resolved = self.block(body);
+                *locals = std::mem::replace(&mut self.frame, outer).names;
+                resolved?;
+            },
+            StmtKind::Global(names) => {
+                for name in names {
+                    let slot = Slot::Global(self.global(&name.name));
+                    name.slot = slot;
+                    if let Some(scope) = self.frame.scopes.last_mut() {
+                        scope.push((name.name.clone(), slot));
+                    }
+                }
+            },
+            StmtKind::Return(Some(value)) | StmtKind::Expr(value) => self.expression(value)?,
+            StmtKind::Return(None) | StmtKind::Break | StmtKind::Continue => {},
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self, expr: &mut Expr) -> Result<(), Error> {
+        match &mut expr.kind {
+            ExprKind::Literal(_) => {},
+            ExprKind::Variable(name) => self.variable(name)?,
+            ExprKind::Unary { operand, .. } => self.expression(operand)?,
+            ExprKind::Binary { left, right, .. } => {
+                self.expression(left)?;
+                self.expression(right)?;
+            },
+            ExprKind::Call { name, args } => {
+                for arg in args {
+                    self.expression(arg)?;
+                }
+                name.slot = Slot::Global(self.functions.slot(&name.name));
+            },
+            ExprKind::Grouping(inner) => self.expression(inner)?,
+        }
+        Ok(())
+    }
+}
@@ -1,6 +1,18 @@
+//! Splits source text into the [`Token`]s read by [`crate::parser`].
+//!
+//! Keywords, names and numbers are words, whose case is handled by the [`Dialect`].
+//! Comments don't become tokens but are kept, as trivia, on the token after them.
+//! Every token knows the [`Span`] it was read from.
+use crate::error::{Error, ScanError};
+use crate::value::Value;
+use std::borrow::Cow;
 use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
 //use std::collections::HashMap;
 
+/// What a token is. Keywords get a type of their own, names are `Identifier` and
+/// numbers and strings are `Literal`.
 #[derive(Debug)]
 #[derive(PartialEq)]
 pub enum TokenType {
@@ -9,6 +21,7 @@ pub enum TokenType {
     Assign,
     Output,
     Separator,
+    Semicolon,
     If,
     Else,
     ElseIf,
@@ -16,13 +29,20 @@ pub enum TokenType {
     ReturnValue,
     ForLoop,
     WhileLoop,
+    Break,
+    Continue,
+    Global,
     EndScope,
     AddOperator,
     MinusOperator,
+    /// A `-` with no operand before it, `-X`.
+    NegateOperator,
     MultOperator,
     ModOperator,
     ExpOperator,
+    ConcatOperator,
     DivOperator,
+    IntDivOperator,
     LessThan,
     LessThanEq,
     GreaterThan,
@@ -35,6 +55,7 @@ pub enum TokenType {
     LParen,
     RParen,
     ToOperator,
+    StepOperator,
     Operator,
     TrueLiteral,
     FalseLiteral,
@@ -42,139 +63,585 @@ pub enum TokenType {
     Newline,
 }
 
+/// Where a piece of source starts (byte offset, line and column, both counted from 1)
+/// and how many bytes it covers.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub offset: usize,
+    pub line: usize,
+    pub col: usize,
+    pub len: usize,
+}
+
+impl Span {
+    /// A span from the start of `self` to the end of `other`.
+    pub fn to(self, other: Span) -> Span {
+        let end = (other.offset + other.len).max(self.offset + self.len);
+        Span {
+            len: end - self.offset,
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+/// A token and the source it was read from.
 #[derive(Debug)]
 pub struct Token<'a> {
     pub tk_type: TokenType,
-    pub lexeme: &'a str,
-    //pub row: usize,
-    //pub col: usize,
+    /// The source text of the token, except for names folded to uppercase.
+    pub lexeme: Cow<'a, str>,
+    pub span: Span,
+    /// Comments between the previous token and this one. Comments after the last
+    /// token of the input are kept on it too.
+    pub trivia: Vec<Comment<'a>>,
+}
+
+/// How the scanner treats the case of letters, chosen for every run.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Dialect {
+    /// Keywords are only known in uppercase and names keep their case.
+    #[default]
+    Uppercase,
+    /// Keywords in any case, names are folded to uppercase so `x` and `X` are one
+    /// variable.
+    FoldCase,
+    /// Keywords in any case, names keep their case so `x` and `X` are two variables.
+    KeepCase,
+}
+
+impl Dialect {
+    /// The dialect named by `--case` on the command line: `upper`, `fold` or `keep`.
+    pub fn from_name(name: &str) -> Option<Dialect> {
+        match name {
+            "upper" => Some(Dialect::Uppercase),
+            "fold" => Some(Dialect::FoldCase),
+            "keep" => Some(Dialect::KeepCase),
+            _ => None,
+        }
+    }
+
+    // the token type and lexeme of a keyword, name or number
+    fn word<'a>(self, word: &'a str) -> (TokenType, Cow<'a, str>) {
+        if self == Dialect::Uppercase {
+            return (TokenType::token_type(word).unwrap(), Cow::Borrowed(word));
+        }
+        let tk_type = TokenType::token_type(&word.to_ascii_uppercase()).unwrap();
+        if tk_type == TokenType::Identifier && self == Dialect::FoldCase && word.chars().any(char::is_lowercase) {
+            return (tk_type, Cow::Owned(word.to_uppercase()));
+        }
+        (tk_type, Cow::Borrowed(word))
+    }
+
+    fn is_rem(self, word: &str) -> bool {
+        match self {
+            Dialect::Uppercase => word == "REM",
+            _ => word.eq_ignore_ascii_case("REM"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommentKind {
+    /// `REM`, up to the end of the line.
+    Rem,
+    /// `'`, up to the end of the line.
+    Apostrophe,
+    /// `/* ... */`, can span lines.
+    Block,
+}
+
+/// A comment and the whole source it covers, markers included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment<'a> {
+    pub kind: CommentKind,
+    pub text: &'a str,
+    pub span: Span,
 }
 
 
 impl<'a> fmt::Display for Token<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Token<{:?}, {:?}>", self.tk_type, self.lexeme)
+        write!(f, "Token<{:?}, {:?}, {}>", self.tk_type, self.lexeme, self.span)
+    }
+}
+
+impl<'a> fmt::Display for Comment<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Comment<{:?}, {:?}, {}>", self.kind, self.text, self.span)
+    }
+}
+
+// where the next character of the input is. the cursor walks the input once, so
+// the line and column of a token are known without counting from the line start
+#[derive(Debug, Clone, Copy)]
+struct Mark {
+    offset: usize,
+    line: usize,
+    col: usize,
+}
+
+// steps through the characters of the input, keeping track of lines and columns
+struct Cursor<'a> {
+    input: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    line: usize,
+    col: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(input: &'a str) -> Cursor<'a> {
+        Cursor {
+            input,
+            chars: input.char_indices().peekable(),
+            line: 1,
+            col: 1,
+        }
+    }
+
+    fn offset(&mut self) -> usize {
+        self.chars.peek().map_or(self.input.len(), |&(idx, _)| idx)
+    }
+
+    fn mark(&mut self) -> Mark {
+        Mark {
+            offset: self.offset(),
+            line: self.line,
+            col: self.col,
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    // the input from the next character on
+    fn rest(&mut self) -> &'a str {
+        let offset = self.offset();
+        &self.input[offset..]
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let (_, c) = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+        Some(c)
+    }
+
+    // moves on to the byte offset end, which has to be at a character boundary
+    fn bump_to(&mut self, end: usize) {
+        while self.offset() < end {
+            self.bump();
+        }
+    }
+
+    // moves on to the end of the line, right before the newline
+    fn bump_line(&mut self) {
+        while self.peek().is_some_and(|c| c != '\n') {
+            self.bump();
+        }
+    }
+
+    // the source from start up to the cursor
+    fn since(&mut self, start: Mark) -> (&'a str, Span) {
+        let end = self.offset();
+        let span = Span {
+            offset: start.offset,
+            line: start.line,
+            col: start.col,
+            len: end - start.offset,
+        };
+        (&self.input[start.offset..end], span)
     }
 }
 
 impl TokenType {
+    /// The type of a keyword or symbol as written, `Identifier` for anything else.
+    #[allow(clippy::self_named_constructors)]
     pub fn token_type(value: &str) -> Option<TokenType> {
-        if value.parse::<f64>().is_ok() {
-            return Some(TokenType::Literal);
-        }
         match value {
-            "\n" => return Some(TokenType::Newline),
-            "LET" => return Some(TokenType::Initialize),
-            "END" => return Some(TokenType::EndScope),
-            "BE" => return Some(TokenType::Assign),
-            "RETURN" => return Some(TokenType::ReturnValue),
-            "IF" => return Some(TokenType::If),
-            "ELSE" => return Some(TokenType::Else),
-            "ELIF" => return Some(TokenType::ElseIf),
-            "+" => return Some(TokenType::AddOperator),
-            "-" => return Some(TokenType::MinusOperator),
-            "*" => return Some(TokenType::MultOperator),
-            "/" => return Some(TokenType::DivOperator),
-            "%" => return Some(TokenType::ModOperator),
-            "^" => return Some(TokenType::ExpOperator),
-            "(" => return Some(TokenType::LParen),
-            ")" => return Some(TokenType::RParen),
-            "<" => return Some(TokenType::LessThan),
-            "<=" => return Some(TokenType::LessThanEq),
-            ">" => return Some(TokenType::GreaterThan),
-            ">=" => return Some(TokenType::GreaterThanEq),
-            "AND" => return Some(TokenType::AndOperator),
-            "OR" => return Some(TokenType::OrOperator),
-            "NOT" => return Some(TokenType::NotOperator),
-            "EQUALS" => return Some(TokenType::EqualsOperator),
-            "NOTEQUALS" => return Some(TokenType::NotEqualsOperator),
-            "PRINT" => return Some(TokenType::Output),
-            "WHILE" => return Some(TokenType::WhileLoop),
-            "FOR" => return Some(TokenType::ForLoop),
-            "IN" => return Some(TokenType::Operator),
-            "TO" => return Some(TokenType::ToOperator),
-            "FUNCTION" => return Some(TokenType::Function),
-            "," => return Some(TokenType::Separator),
-            "TRUE" => return Some(TokenType::TrueLiteral),
-            "FALSE" => return Some(TokenType::FalseLiteral),
-            _ => return Some(TokenType::Identifier),
+            "\n" => Some(TokenType::Newline),
+            "LET" => Some(TokenType::Initialize),
+            "END" => Some(TokenType::EndScope),
+            "BE" => Some(TokenType::Assign),
+            "RETURN" => Some(TokenType::ReturnValue),
+            "IF" => Some(TokenType::If),
+            "ELSE" => Some(TokenType::Else),
+            "ELIF" => Some(TokenType::ElseIf),
+            "+" => Some(TokenType::AddOperator),
+            "-" => Some(TokenType::MinusOperator),
+            "*" => Some(TokenType::MultOperator),
+            "/" => Some(TokenType::DivOperator),
+            "\\" => Some(TokenType::IntDivOperator),
+            "%" => Some(TokenType::ModOperator),
+            "MOD" => Some(TokenType::ModOperator),
+            "^" => Some(TokenType::ExpOperator),
+            "&" => Some(TokenType::ConcatOperator),
+            "(" => Some(TokenType::LParen),
+            ")" => Some(TokenType::RParen),
+            "<" => Some(TokenType::LessThan),
+            "<=" => Some(TokenType::LessThanEq),
+            ">" => Some(TokenType::GreaterThan),
+            ">=" => Some(TokenType::GreaterThanEq),
+            "=" => Some(TokenType::EqualsOperator),
+            "<>" => Some(TokenType::NotEqualsOperator),
+            "AND" => Some(TokenType::AndOperator),
+            "OR" => Some(TokenType::OrOperator),
+            "NOT" => Some(TokenType::NotOperator),
+            "EQUALS" => Some(TokenType::EqualsOperator),
+            "NOTEQUALS" => Some(TokenType::NotEqualsOperator),
+            "PRINT" => Some(TokenType::Output),
+            "WHILE" => Some(TokenType::WhileLoop),
+            "FOR" => Some(TokenType::ForLoop),
+            "BREAK" => Some(TokenType::Break),
+            "CONTINUE" => Some(TokenType::Continue),
+            "GLOBAL" => Some(TokenType::Global),
+            "IN" => Some(TokenType::Operator),
+            "TO" => Some(TokenType::ToOperator),
+            "STEP" => Some(TokenType::StepOperator),
+            "FUNCTION" => Some(TokenType::Function),
+            "," => Some(TokenType::Separator),
+            ";" => Some(TokenType::Semicolon),
+            "TRUE" => Some(TokenType::TrueLiteral),
+            "FALSE" => Some(TokenType::FalseLiteral),
+            _ => Some(TokenType::Identifier),
         }
     }
 }
 
 
-pub fn scan(input_string: &String) -> Result<Vec<Token>, String> {
-    let mut tokens: Vec<Token> = Vec::new();
-    // delimiters seperate keywords, literals, operators, etc..
-    let delimiters = vec![' ', ',', '(', ')', '\n', '\t', '\r', '"'];
-    // whitespaces get ignore and skipped unless in_string boolean is true
-    let whitespaces = vec![' ', '\t', '\r'];
-    // for strings
-    let mut in_string = false;
-    let mut start_idx: usize = 0;
-    let mut end_idx: usize = 0;
-    //let mut col: usize = 0;
-    
-    while end_idx < input_string.len() {
-        let curr = input_string.chars().nth(end_idx).unwrap();
-        let curr_str = &input_string[end_idx..end_idx+1];
-
-        if curr == '"' && !in_string {
-            // start of a string
-            if start_idx != end_idx {
-                // add previous token
-                let value = &input_string[start_idx..end_idx];
-                tokens.push(Token {
-                    tk_type: TokenType::token_type(curr_str).unwrap(),
-                    lexeme: value,
-                });
-            }
-            in_string = true;
-            // including the quotation mark as the start of token
-            start_idx = end_idx;
+// characters that end a word, besides operators
+const DELIMITERS: [char; 10] = [' ', ',', ';', '(', ')', '\n', '\t', '\r', '"', '\''];
+
+// operators also end the token before them, so X+1 is three tokens
+const OPERATORS: [char; 11] = ['+', '-', '*', '/', '\\', '%', '^', '&', '<', '>', '='];
+
+// the longest operator at the start of rest, <= rather than < and =
+fn operator_len(rest: &str) -> usize {
+    if ["<=", ">=", "<>"].iter().any(|operator| rest.starts_with(operator)) { 2 } else { 1 }
+}
+
+// the sign of an exponent belongs to its number, 1E-5 is a single literal
+fn exponent_sign(word: &str, curr: char) -> bool {
+    let Some(mantissa) = word.strip_suffix(['e', 'E']) else {
+        return false;
+    };
+    (curr == '+' || curr == '-') && starts_number(word) && radix(mantissa).0 == 10 && read_number(mantissa).is_ok()
+}
+
+// whether a word has to be read as a number, names can't start with a digit
+fn starts_number(word: &str) -> bool {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some('.') => chars.next().is_some_and(|c| c.is_ascii_digit()),
+        Some(c) => c.is_ascii_digit(),
+        None => false,
+    }
+}
+
+// whether an & that doesn't follow an operand starts a number, &HFF
+fn ampersand_number(rest: &str) -> bool {
+    let mut chars = rest.chars().skip(1);
+    chars.next().is_some_and(|c| matches!(c, 'H' | 'h' | 'O' | 'o' | 'B' | 'b'))
+        && chars.next().is_some_and(|c| c.is_ascii_alphanumeric())
+}
+
+// why a numeric literal can't be read
+#[derive(Debug)]
+enum BadNumber {
+    Malformed,
+    TooLarge,
+}
+
+// the radix of a numeric literal and its digits after the prefix
+fn radix(lexeme: &str) -> (u32, &str) {
+    let prefix = lexeme.get(..2).map(str::to_ascii_uppercase);
+    match prefix.as_deref() {
+        Some("0X" | "&H") => (16, &lexeme[2..]),
+        Some("0O" | "&O") => (8, &lexeme[2..]),
+        Some("0B" | "&B") => (2, &lexeme[2..]),
+        _ => (10, lexeme),
+    }
+}
+
+// the digits of a group without its separators. _ can only go between two digits
+fn grouped(digits: &str, radix: u32) -> Result<String, BadNumber> {
+    let valid = !digits.is_empty()
+        && !digits.starts_with('_')
+        && !digits.ends_with('_')
+        && !digits.contains("__")
+        && digits.chars().all(|c| c == '_' || c.is_digit(radix));
+    if !valid {
+        return Err(BadNumber::Malformed);
+    }
+    Ok(digits.replace('_', ""))
+}
+
+// decimal numbers have a whole part, a fraction or both and an optional exponent,
+// 1_000, 2.5, .5, 1E-3. other radixes only have whole digits, 0xFF, &HFF, 0b1010.
+// whole numbers are integers, anything with a fraction or an exponent is a float
+fn read_number(lexeme: &str) -> Result<Value, BadNumber> {
+    let (radix, digits) = radix(lexeme);
+    if radix != 10 {
+        let digits = grouped(digits, radix)?;
+        // the digits are valid, so the only way to fail is to overflow
+        return i64::from_str_radix(&digits, radix).map(Value::Integer).map_err(|_| BadNumber::TooLarge);
+    }
+    let (mantissa, exponent) = match lexeme.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (lexeme, None),
+    };
+    let mut text = match mantissa.split_once('.') {
+        Some(("", fraction)) => std::format!("0.{}", grouped(fraction, 10)?),
+        Some((whole, "")) => grouped(whole, 10)?,
+        Some((whole, fraction)) => std::format!("{}.{}", grouped(whole, 10)?, grouped(fraction, 10)?),
+        None if exponent.is_none() => {
+            return grouped(mantissa, 10)?.parse::<i64>().map(Value::Integer).map_err(|_| BadNumber::TooLarge);
+        },
+        None => grouped(mantissa, 10)?,
+    };
+    if let Some(exponent) = exponent {
+        let (sign, digits) = match exponent.strip_prefix(['+', '-']) {
+            Some(digits) => (&exponent[..1], digits),
+            None => ("", exponent),
+        };
+        text = std::format!("{}e{}{}", text, sign, grouped(digits, 10)?);
+    }
+    match text.parse::<f64>() {
+        Ok(value) if value.is_finite() => Ok(Value::Float(value)),
+        Ok(_) => Err(BadNumber::TooLarge),
+        Err(_) => Err(BadNumber::Malformed),
+    }
+}
+
+/// The value of a numeric literal made by the scanner.
+pub fn number(lexeme: &str) -> Option<Value> {
+    read_number(lexeme).ok()
+}
+
+// moves past the string whose opening quote is next. a quote is written \" or "",
+// escapes are checked here so that unescape can't fail. a string ends on its own
+// line, so one that is never closed is reported at its opening quote instead of
+// swallowing the strings after it
+fn string(cursor: &mut Cursor) -> Result<(), Error> {
+    let start = cursor.mark();
+    let unclosed = || -> Result<(), Error> {
+        let span = Span { offset: start.offset, line: start.line, col: start.col, len: 1 };
+        Err(ScanError::UnterminatedString { span }.into())
+    };
+    cursor.bump();
+    loop {
+        let at = cursor.mark();
+        match cursor.bump() {
+            None | Some('\n') => return unclosed(),
+            Some('\\') if matches!(cursor.peek(), None | Some('\n')) => return unclosed(),
+            Some('"') if cursor.peek() == Some('"') => {
+                cursor.bump();
+            },
+            Some('"') => return Ok(()),
+            Some('\\') => {
+                cursor.bump_to(escape_end(cursor.input, at.offset));
+                let (escape, span) = cursor.since(at);
+                if escaped(escape).is_none() {
+                    return Err(ScanError::InvalidEscape { escape: escape.to_string(), span }.into());
+                }
+            },
+            Some(_) => {},
         }
-        else if curr == '"' && in_string {
-            // end of a string
-            end_idx += 1;
-            let value = &input_string[start_idx..end_idx];
-            tokens.push(Token {
-                tk_type: TokenType::Literal,
-                lexeme: value,
-            });
-            start_idx = end_idx + 1; // added +1
-            in_string = false;
+    }
+}
+
+// where the escape starting with the backslash at start ends, \u{...} runs to its
+// closing brace when it has one on the same line
+fn escape_end(input_string: &str, start: usize) -> usize {
+    let rest = &input_string[start + 1..];
+    let Some(next) = rest.chars().next() else {
+        return start + 1;
+    };
+    if rest.starts_with("u{") {
+        if let Some(close) = rest.find(['}', '\n', '"']).filter(|&close| rest.as_bytes()[close] == b'}') {
+            return start + 1 + close + 1;
         }
-        else if delimiters.contains(&curr) && !in_string {
-            // current character is a delimiter
-            if start_idx != end_idx {
-                // at least a seperation of 1
-                let value = &input_string[start_idx..end_idx];
-                tokens.push(Token {
-                    tk_type: TokenType::token_type(value).unwrap(),
-                    lexeme: value,
-                });
-            }
-            // advancing to the next character
-            start_idx = end_idx + 1;
-            if !whitespaces.contains(&curr) {
-                // current character is not whitespace
-                tokens.push(Token {
-                    tk_type: TokenType::token_type(curr_str).unwrap(),
-                    lexeme: curr_str,
-                });
+    }
+    start + 1 + next.len_utf8()
+}
+
+// the character an escape stands for, None when it isn't one
+fn escaped(escape: &str) -> Option<char> {
+    match escape {
+        "\\n" => Some('\n'),
+        "\\t" => Some('\t'),
+        "\\\"" => Some('"'),
+        "\\\\" => Some('\\'),
+        _ => {
+            let digits = escape.strip_prefix("\\u{")?.strip_suffix('}')?;
+            if digits.is_empty() || digits.len() > 6 {
+                return None;
             }
+            char::from_u32(u32::from_str_radix(digits, 16).ok()?)
+        },
+    }
+}
+
+/// The text of a string literal, without its quotes and with its escapes replaced.
+pub fn unescape(literal: &str) -> String {
+    let inner = literal.strip_prefix('"').unwrap_or(literal);
+    let inner = inner.strip_suffix('"').unwrap_or(inner);
+    let mut text = String::with_capacity(inner.len());
+    let mut idx = 0;
+    while let Some(c) = inner[idx..].chars().next() {
+        match c {
+            '\\' => {
+                let end = escape_end(inner, idx);
+                // the scanner only lets valid escapes through
+                text.extend(escaped(&inner[idx..end]));
+                idx = end;
+            },
+            '"' => {
+                // "" stands for one quote
+                text.push('"');
+                idx = (idx + 2).min(inner.len());
+            },
+            c => {
+                text.push(c);
+                idx += c.len_utf8();
+            },
         }
-        // character is not a delimiter, so we continue until one appears
-        end_idx += 1;
-    }
-    //for t in &tokens {
-      //  println!("{}", t);
-    //}
-    return Ok(tokens);
-    //let result = evaluate(&tokens);
-    //if result.is_err() {
-        //println!("ERROR: {}", result.err().unwrap());
-    //}
+    }
+    text
+}
+
+// the tokens found so far and the comments waiting for the token they come before
+#[derive(Default)]
+struct Output<'a> {
+    tokens: Vec<Token<'a>>,
+    comments: Vec<Comment<'a>>,
+}
+
+impl<'a> Output<'a> {
+    fn token(&mut self, tk_type: TokenType, (lexeme, span): (impl Into<Cow<'a, str>>, Span)) {
+        self.tokens.push(Token {
+            tk_type,
+            lexeme: lexeme.into(),
+            span,
+            trivia: std::mem::take(&mut self.comments),
+        });
+    }
+
+    // whether the last token can end an operand, a '-' after one subtracts and a
+    // '-' anywhere else negates
+    fn after_operand(&self) -> bool {
+        self.tokens.last().is_some_and(|token| matches!(token.tk_type,
+            TokenType::Identifier | TokenType::Literal | TokenType::TrueLiteral
+                | TokenType::FalseLiteral | TokenType::RParen))
+    }
+
+    fn comment(&mut self, kind: CommentKind, (text, span): (&'a str, Span)) {
+        self.comments.push(Comment { kind, text, span });
+    }
+
+    fn finish(mut self) -> Vec<Token<'a>> {
+        if let Some(last) = self.tokens.last_mut() {
+            last.trivia.append(&mut self.comments);
+        }
+        self.tokens
+    }
+}
+
+/// Splits the input into tokens, with keywords in uppercase.
+pub fn scan(input_string: &str) -> Result<Vec<Token<'_>>, Error> {
+    scan_with(input_string, Dialect::default())
+}
+
+/// Splits the input into tokens in a single pass over its characters, treating
+/// the case of letters the way `dialect` says.
+pub fn scan_with(input_string: &str, dialect: Dialect) -> Result<Vec<Token<'_>>, Error> {
+    let mut cursor = Cursor::new(input_string);
+    let mut output = Output::default();
+
+    while let Some(curr) = cursor.peek() {
+        let start = cursor.mark();
+        match curr {
+            // whitespaces get ignored and skipped
+            ' ' | '\t' | '\r' => {
+                cursor.bump();
+            },
+            '\'' => {
+                cursor.bump_line();
+                output.comment(CommentKind::Apostrophe, cursor.since(start));
+            },
+            '/' if cursor.rest().starts_with("/*") => {
+                match cursor.rest()[2..].find("*/") {
+                    Some(close) => cursor.bump_to(start.offset + 2 + close + 2),
+                    None => {
+                        let span = Span { len: 2, ..cursor.since(start).1 };
+                        return Err(ScanError::UnterminatedComment { span }.into());
+                    },
+                }
+                output.comment(CommentKind::Block, cursor.since(start));
+            },
+            // the whole string, quotes included, is one literal
+            '"' => {
+                string(&mut cursor)?;
+                output.token(TokenType::Literal, cursor.since(start));
+            },
+            '\n' | ',' | ';' | '(' | ')' => {
+                cursor.bump();
+                let (lexeme, span) = cursor.since(start);
+                output.token(TokenType::token_type(lexeme).unwrap(), (lexeme, span));
+            },
+            _ if OPERATORS.contains(&curr) && !(curr == '&' && !output.after_operand() && ampersand_number(cursor.rest())) => {
+                let len = operator_len(cursor.rest());
+                cursor.bump_to(start.offset + len);
+                let (lexeme, span) = cursor.since(start);
+                let tk_type = match TokenType::token_type(lexeme).unwrap() {
+                    TokenType::MinusOperator if !output.after_operand() => TokenType::NegateOperator,
+                    tk_type => tk_type,
+                };
+                output.token(tk_type, (lexeme, span));
+            },
+            // keywords, names and numbers run until a delimiter or an operator
+            _ => {
+                cursor.bump();
+                while let Some(next) = cursor.peek() {
+                    let word = &input_string[start.offset..cursor.offset()];
+                    if (DELIMITERS.contains(&next) || OPERATORS.contains(&next)) && !exponent_sign(word, next) {
+                        break;
+                    }
+                    cursor.bump();
+                }
+                let (word, span) = cursor.since(start);
+                if starts_number(word) || word.starts_with('&') {
+                    match read_number(word) {
+                        Ok(_) => output.token(TokenType::Literal, (word, span)),
+                        Err(BadNumber::Malformed) => {
+                            return Err(ScanError::MalformedNumber { lexeme: word.to_string(), span }.into());
+                        },
+                        Err(BadNumber::TooLarge) => {
+                            return Err(ScanError::NumberTooLarge { lexeme: word.to_string(), span }.into());
+                        },
+                    }
+                } else if dialect.is_rem(word) {
+                    // REM comments out the rest of the line
+                    cursor.bump_line();
+                    output.comment(CommentKind::Rem, cursor.since(start));
+                } else {
+                    let (tk_type, lexeme) = dialect.word(word);
+                    output.token(tk_type, (lexeme, span));
+                }
+            },
+        }
+    }
+    Ok(output.finish())
 }
 
 /*
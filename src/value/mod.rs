@@ -0,0 +1,47 @@
+//! The values a program works with.
+use std::fmt;
+
+/// A value while the program runs, strings are kept without their quotes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    /// A number written without a decimal point or an exponent, and anything worked
+    /// out from integers alone.
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+}
+
+impl Value {
+    /// The name used in type errors.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "STRING",
+            Value::Integer(_) => "INTEGER",
+            Value::Float(_) => "FLOAT",
+            Value::Boolean(_) => "BOOLEAN",
+        }
+    }
+
+    /// The value of a number as a float, `None` for anything else.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Integer(integer) => Some(*integer as f64),
+            Value::Float(float) => Some(*float),
+            _ => None,
+        }
+    }
+}
+
+// the way PRINT shows a value
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::String(text) => write!(f, "{}", text),
+            Value::Integer(integer) => write!(f, "{}", integer),
+            Value::Float(float) => write!(f, "{}", float),
+            Value::Boolean(true) => write!(f, "TRUE"),
+            Value::Boolean(false) => write!(f, "FALSE"),
+        }
+    }
+}
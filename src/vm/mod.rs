@@ -0,0 +1,289 @@
+//! Runs the bytecode from [`crate::compiler::compile`].
+//!
+//! The VM gives the same output and the same errors as [`crate::executer::Interpreter`]
+//! running the tree the program was compiled from, without walking the tree again on
+//! every pass of a loop.
+use crate::ast::BinaryOp;
+use crate::bytecode::{LoopPart, Op, Program};
+use crate::error::{Error, RuntimeError};
+use crate::executer::{self, DEFAULT_MAX_DEPTH, PRINT_ZONE};
+use crate::scanner::Span;
+use crate::value::Value;
+use std::io::{self, Write};
+
+// an active FUNCTION call
+struct Frame {
+    function: usize,
+    // local slots, None until the slot gets a value
+    slots: Vec<Option<Value>>,
+    // the stack height when the call started, everything above it belongs to the call
+    base: usize,
+    // index of the CALL instruction
+    call: usize,
+}
+
+/// A virtual machine that keeps the cursor column of PRINT between programs.
+pub struct Vm {
+    max_depth: usize,
+    max_passes: Option<usize>,
+    // column PRINT left the cursor at, only non-zero after a trailing ',' or ';'
+    column: usize,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn binary_op(op: Op) -> Option<BinaryOp> {
+    match op {
+        Op::Add => Some(BinaryOp::Add),
+        Op::Subtract => Some(BinaryOp::Subtract),
+        Op::Multiply => Some(BinaryOp::Multiply),
+        Op::Divide => Some(BinaryOp::Divide),
+        Op::IntDivide => Some(BinaryOp::IntDivide),
+        Op::Modulo => Some(BinaryOp::Modulo),
+        Op::Power => Some(BinaryOp::Power),
+        Op::Concat => Some(BinaryOp::Concat),
+        Op::Equal => Some(BinaryOp::Equal),
+        Op::NotEqual => Some(BinaryOp::NotEqual),
+        Op::Less => Some(BinaryOp::Less),
+        Op::LessEqual => Some(BinaryOp::LessEqual),
+        Op::Greater => Some(BinaryOp::Greater),
+        Op::GreaterEqual => Some(BinaryOp::GreaterEqual),
+        Op::And => Some(BinaryOp::And),
+        Op::Or => Some(BinaryOp::Or),
+        _ => None,
+    }
+}
+
+fn undefined(program: &Program, name: u16, span: Span) -> Error {
+    RuntimeError::UndefinedSymbol { name: program.names[name as usize].clone(), span }.into()
+}
+
+// the counter, bound and step of the FOR loop on top of the stack
+fn for_state(stack: &[(Value, Span)]) -> (&Value, &Value, &Value) {
+    let value = |idx: usize| &stack[stack.len() - idx].0;
+    (value(3), value(2), value(1))
+}
+
+// the local slots of the current call, or of the top level code, and their names
+fn local_frame<'a>(program: &'a Program, frames: &'a mut [Frame], main: &'a mut [Option<Value>]) -> (&'a mut [Option<Value>], &'a [u16]) {
+    match frames.last_mut() {
+        Some(frame) => (&mut frame.slots, &program.functions[frame.function].locals),
+        None => (main, &program.locals),
+    }
+}
+
+impl Vm {
+    pub fn new() -> Vm {
+        Vm {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_passes: None,
+            column: 0,
+        }
+    }
+
+    /// Limits how deep FUNCTION calls (and recursion) can go.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// Stops a run once its loops jumped back for another pass this many times.
+    /// There is no limit by default.
+    pub fn set_max_passes(&mut self, max_passes: usize) {
+        self.max_passes = Some(max_passes);
+    }
+
+    /// Runs a program from its first instruction to [`Op::Halt`].
+    pub fn run(&mut self, program: &Program) -> Result<(), Error> {
+        let mut globals: Vec<Option<Value>> = vec![None; program.names.len()];
+        // the function each name points at once its definition has run
+        let mut functions: Vec<Option<usize>> = vec![None; program.names.len()];
+        // every value keeps the span of the expression it came from, for type errors
+        let mut stack: Vec<(Value, Span)> = Vec::new();
+        let mut frames: Vec<Frame> = Vec::new();
+        // locals of the blocks of the top level code
+        let mut main: Vec<Option<Value>> = vec![None; program.locals.len()];
+        // pending output of the PRINT being run by the top level code and by every active
+        // call, so a PRINT inside a function doesn't end the line its caller is building
+        let mut output: Vec<String> = vec![String::new()];
+        let mut ip = 0;
+        // every jump back to an earlier instruction is a loop starting another pass
+        let mut passes = 0;
+
+        loop {
+            let op = program.code[ip];
+            let origin = program.origins[ip];
+            ip += 1;
+            match op {
+                Op::Constant(n) => stack.push((program.constants[n as usize].clone(), origin.value)),
+                Op::Load(n) => match &globals[n as usize] {
+                    Some(value) => stack.push((value.clone(), origin.value)),
+                    None => return Err(undefined(program, n, origin.at)),
+                },
+                Op::Store(n) => {
+                    let (value, _) = stack.pop().unwrap();
+                    match &mut globals[n as usize] {
+                        Some(global) => *global = value,
+                        None => return Err(undefined(program, n, origin.at)),
+                    }
+                },
+                Op::Define(n) => globals[n as usize] = Some(stack.pop().unwrap().0),
+                Op::LoadLocal(slot) => {
+                    let (slots, locals) = local_frame(program, &mut frames, &mut main);
+                    match &slots[slot as usize] {
+                        Some(value) => stack.push((value.clone(), origin.value)),
+                        None => return Err(undefined(program, locals[slot as usize], origin.at)),
+                    }
+                },
+                Op::StoreLocal(slot) => {
+                    let (value, _) = stack.pop().unwrap();
+                    let (slots, locals) = local_frame(program, &mut frames, &mut main);
+                    match &mut slots[slot as usize] {
+                        Some(local) => *local = value,
+                        None => return Err(undefined(program, locals[slot as usize], origin.at)),
+                    }
+                },
+                Op::DefineLocal(slot) => {
+                    let value = stack.pop().unwrap().0;
+                    local_frame(program, &mut frames, &mut main).0[slot as usize] = Some(value);
+                },
+                Op::Pop => {
+                    stack.pop();
+                },
+                Op::Not => {
+                    let operand = stack.pop().unwrap();
+                    stack.push((Value::Boolean(!executer::boolean(operand)?), origin.value));
+                },
+                Op::Negate => {
+                    let operand = stack.pop().unwrap();
+                    stack.push((executer::negate(origin.at, operand)?, origin.value));
+                },
+                Op::Jump(target) => {
+                    if (target as usize) < ip {
+                        passes += 1;
+                        if let Some(max_passes) = self.max_passes.filter(|max_passes| passes > *max_passes) {
+                            return Err(RuntimeError::PassLimitExceeded { max_passes, span: origin.at }.into());
+                        }
+                    }
+                    ip = target as usize;
+                },
+                Op::JumpIfFalse(target) => {
+                    if !executer::boolean(stack.pop().unwrap())? {
+                        ip = target as usize;
+                    }
+                },
+                Op::ForNumber(part) => {
+                    let (value, span) = stack.last().unwrap();
+                    match value {
+                        step if part == LoopPart::Step && step.as_float() == Some(0.0) => {
+                            return Err(RuntimeError::ZeroStep { span: *span }.into());
+                        },
+                        Value::Integer(_) | Value::Float(_) => {},
+                        other => {
+                            let expected = std::format!("NUMBER FOR THE FOR LOOP {}", part.name());
+                            return Err(executer::mismatch(&expected, other, *span));
+                        },
+                    }
+                },
+                Op::ForTest(target) => {
+                    let (counter, to, step) = for_state(&stack);
+                    if executer::for_continues(counter, to, step) {
+                        stack.push((counter.clone(), origin.value));
+                    } else {
+                        ip = target as usize;
+                    }
+                },
+                Op::ForStep(target) => {
+                    let (counter, to, step) = for_state(&stack);
+                    match executer::for_step(origin.at, counter, to, step)? {
+                        Some(next) => {
+                            let idx = stack.len() - 3;
+                            stack[idx].0 = next;
+                        },
+                        None => ip = target as usize,
+                    }
+                },
+                Op::DefineFunction(n) => {
+                    let name = program.functions[n as usize].name;
+                    functions[name as usize] = Some(n as usize);
+                },
+                Op::Call { name, argc } | Op::CallDiscard { name, argc } => {
+                    let function = match functions[name as usize] {
+                        Some(function) => function,
+                        None => {
+                            let name = program.names[name as usize].clone();
+                            return Err(RuntimeError::UndefinedFunction { name, span: origin.at }.into());
+                        },
+                    };
+                    let info = &program.functions[function];
+                    if argc != info.arity {
+                        return Err(RuntimeError::ArityMismatch {
+                            function: program.names[name as usize].clone(),
+                            expected: info.arity as usize,
+                            found: argc as usize,
+                            span: origin.at,
+                        }.into());
+                    }
+                    if frames.len() >= self.max_depth {
+                        let function = program.names[name as usize].clone();
+                        return Err(RuntimeError::CallDepthExceeded { function, max_depth: self.max_depth, span: origin.at }.into());
+                    }
+                    let base = stack.len() - argc as usize;
+                    let mut slots: Vec<Option<Value>> = stack.drain(base..).map(|(value, _)| Some(value)).collect();
+                    slots.resize(info.locals.len(), None);
+                    frames.push(Frame { function, slots, base, call: ip - 1 });
+                    output.push(String::new());
+                    ip = info.start as usize;
+                },
+                Op::Return | Op::ReturnNone => {
+                    let value = match op {
+                        Op::Return => Some(stack.pop().unwrap().0),
+                        _ => None,
+                    };
+                    let frame = frames.pop().unwrap();
+                    output.pop();
+                    stack.truncate(frame.base);
+                    let call = program.origins[frame.call];
+                    match (program.code[frame.call], value) {
+                        (Op::Call { .. }, Some(value)) => stack.push((value, call.value)),
+                        (Op::Call { name, .. }, None) => {
+                            let function = program.names[name as usize].clone();
+                            return Err(RuntimeError::NoReturnValue { function, span: call.value }.into());
+                        },
+                        _ => {},
+                    }
+                    ip = frame.call + 1;
+                },
+                Op::Print => {
+                    let (value, _) = stack.pop().unwrap();
+                    executer::write(&mut self.column, output.last_mut().unwrap(), &value.to_string());
+                },
+                Op::PrintZone => {
+                    let padding = PRINT_ZONE - self.column % PRINT_ZONE;
+                    executer::write(&mut self.column, output.last_mut().unwrap(), &" ".repeat(padding));
+                },
+                Op::PrintEnd { newline } => {
+                    let output = output.last_mut().unwrap();
+                    if newline {
+                        output.push('\n');
+                        self.column = 0;
+                    }
+                    let mut stdout = io::stdout();
+                    let _ = stdout.write_all(output.as_bytes());
+                    let _ = stdout.flush();
+                    output.clear();
+                },
+                Op::Halt => return Ok(()),
+                _ => {
+                    let right = stack.pop().unwrap();
+                    let left = stack.pop().unwrap();
+                    let op = binary_op(op).unwrap();
+                    stack.push((executer::binary(op, origin.at, left, right)?, origin.value));
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,50 @@
+// runs the command line the way a shell would and checks how it ends
+use std::io::{BufRead, BufReader, Write};
+use std::process::{self, Command, Stdio};
+use std::{env, fs};
+
+fn command(args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_basic-compiler"));
+    command.args(args).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    command
+}
+
+#[test]
+fn listings_stop_quietly_when_the_reader_does() {
+    let program = "LET X BE 1\nPRINT X + 1\n".repeat(20_000);
+    for listing in ["tokens", "ast", "disasm"] {
+        let mut child = command(&[listing, "-"]).spawn().unwrap();
+        child.stdin.take().unwrap().write_all(program.as_bytes()).unwrap();
+        // like `| head -1`, read one line and close the pipe
+        let mut first = String::new();
+        BufReader::new(child.stdout.take().unwrap()).read_line(&mut first).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "{}: {}", listing, String::from_utf8_lossy(&output.stderr));
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "", "{}", listing);
+    }
+}
+
+#[test]
+fn compiled_programs_are_known_by_their_contents() {
+    let dir = env::temp_dir().join(std::format!("basic-compiler-cli-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let compiled = dir.join("program.bin");
+    let source = dir.join("program.jpoc");
+    fs::write(&source, "PRINT 1 + 1\n").unwrap();
+    let output = command(&["compile", source.to_str().unwrap(), "-o", compiled.to_str().unwrap()]).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    // a source file named like a compiled one, and a compiled one under any name
+    for path in [&source, &compiled] {
+        let output = command(&["run", path.to_str().unwrap()]).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "2\n", "{}", String::from_utf8_lossy(&output.stderr));
+    }
+    let mut child = command(&["run", "-"]).spawn().unwrap();
+    child.stdin.take().unwrap().write_all(&fs::read(&compiled).unwrap()).unwrap();
+    assert_eq!(String::from_utf8_lossy(&child.wait_with_output().unwrap().stdout), "2\n");
+
+    let output = command(&["check", compiled.to_str().unwrap()]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("CHECK NEEDS THE SOURCE OF THE PROGRAM"));
+    fs::remove_dir_all(&dir).unwrap();
+}
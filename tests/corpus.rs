@@ -0,0 +1,90 @@
+// runs every program in tests/corpus through the bytecode VM and the tree-walking
+// executer and checks that both print the same output and report the same errors.
+// programs named error_* have to fail, every other one has to run to the end.
+// what a program prints is kept next to it in a .out file, and the error an error_*
+// program reports in a .err file. run with BLESS=1 to write them from the VM
+use basic_compiler::{bytecode, compiler, parser, scanner};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const CORPUS: &str = "tests/corpus";
+
+fn programs() -> Vec<PathBuf> {
+    let mut programs: Vec<PathBuf> = fs::read_dir(CORPUS)
+        .expect("the corpus directory is missing")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "jpo"))
+        .collect();
+    programs.sort();
+    assert!(!programs.is_empty(), "the corpus is empty");
+    programs
+}
+
+fn run(path: &PathBuf, walk: bool) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_basic-compiler"));
+    command.arg("run");
+    if walk {
+        command.arg("--walk");
+    }
+    command.arg(path).output().expect("could not start the interpreter")
+}
+
+#[test]
+fn vm_matches_tree_walker() {
+    for path in programs() {
+        let vm = run(&path, false);
+        let walk = run(&path, true);
+        let name = path.display();
+        assert_eq!(String::from_utf8_lossy(&vm.stdout), String::from_utf8_lossy(&walk.stdout), "output of {}", name);
+        assert_eq!(String::from_utf8_lossy(&vm.stderr), String::from_utf8_lossy(&walk.stderr), "errors of {}", name);
+        assert_eq!(vm.status.code(), walk.status.code(), "exit code of {}", name);
+
+        let should_fail = path.file_name().unwrap().to_string_lossy().starts_with("error_");
+        assert_eq!(!vm.status.success(), should_fail, "{} {}", name, String::from_utf8_lossy(&vm.stderr));
+    }
+}
+
+// the expected contents of the file next to path with this extension, written from
+// actual instead when blessing. a program that shouldn't fail has no .err file
+fn golden(path: &Path, extension: &str, actual: &[u8]) {
+    let golden = path.with_extension(extension);
+    let actual = String::from_utf8_lossy(actual);
+    if env::var_os("BLESS").is_some() {
+        if extension == "out" || !actual.is_empty() {
+            fs::write(&golden, actual.as_bytes()).unwrap();
+        }
+        return;
+    }
+    let expected = fs::read_to_string(&golden).unwrap_or_default();
+    assert_eq!(actual, expected, "{} differs from {}", extension, golden.display());
+}
+
+#[test]
+fn output_matches_golden_files() {
+    for path in programs() {
+        let vm = run(&path, false);
+        golden(&path, "out", &vm.stdout);
+        golden(&path, "err", &vm.stderr);
+    }
+}
+
+#[test]
+fn every_program_disassembles() {
+    for path in programs() {
+        let source = fs::read_to_string(&path).unwrap();
+        // strings that are never closed and variables that are never given a value
+        // are found before there is anything to disassemble
+        let compiled = scanner::scan(&source).and_then(|tokens| compiler::compile(&parser::parse(&tokens)?));
+        let program = match compiled {
+            Ok(program) => program,
+            Err(error) => {
+                assert!(path.file_name().unwrap().to_string_lossy().starts_with("error_"), "{}", error);
+                continue;
+            },
+        };
+        let listing = bytecode::disassemble(&program);
+        assert_eq!(listing.lines().filter(|line| line.starts_with(|c: char| c.is_ascii_digit())).count(), program.code.len());
+    }
+}
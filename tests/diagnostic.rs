@@ -0,0 +1,81 @@
+// renders errors from real programs and from hand-made spans and checks that the
+// caret lands under the right columns
+use basic_compiler::diagnostic;
+use basic_compiler::error::{Error, RuntimeError};
+use basic_compiler::executer::Interpreter;
+use basic_compiler::scanner::{self, Span};
+use basic_compiler::parser;
+
+// the error a program stops with, from whichever stage finds it
+fn error(source: &str) -> Error {
+    let tokens = scanner::scan(source).unwrap();
+    let program = match parser::parse(&tokens) {
+        Ok(program) => program,
+        Err(error) => return error,
+    };
+    Interpreter::new().execute(&program).unwrap_err()
+}
+
+// the first line of the errors below: the resolver finds a name that is never set,
+// the hand-made ones are raised while running
+const UNDEFINED: &str = "error[E0301]: X SYMBOL NOT FOUND. MAKE SURE TO INITIALIZE WITH 'LET'";
+const UNRESOLVED: &str = "error[E0601]: X IS NEVER GIVEN A VALUE. MAKE SURE TO INITIALIZE WITH 'LET'";
+
+fn undefined(offset: usize, line: usize, col: usize, len: usize) -> Error {
+    Error::Runtime(RuntimeError::UndefinedSymbol { name: "X".to_string(), span: Span { offset, line, col, len } })
+}
+
+#[test]
+fn the_caret_lines_up_after_tabs() {
+    let source = "LET Y BE 1\n\tPRINT\tY, X\n";
+    assert_eq!(
+        diagnostic::render(&error(source), "tabs.jpo", source),
+        UNRESOLVED.to_string() + "\n --> tabs.jpo:2:11\n  |\n2 |     PRINT    Y, X\n  |                 ^",
+    );
+}
+
+#[test]
+fn the_caret_counts_characters_not_bytes() {
+    let source = "PRINT \"héllo wörld\" & X\n";
+    assert_eq!(
+        diagnostic::render(&error(source), "unicode.jpo", source),
+        UNRESOLVED.to_string() + "\n --> unicode.jpo:1:23\n  |\n1 | PRINT \"héllo wörld\" & X\n  |                       ^",
+    );
+}
+
+#[test]
+fn the_caret_stops_at_the_end_of_the_line() {
+    // a span that runs over the newline only underlines what is left of its own line
+    let source = "PRINT XYZ\nPRINT 1\n";
+    assert_eq!(
+        diagnostic::render(&undefined(6, 1, 7, 12), "long.jpo", source),
+        UNDEFINED.to_string() + "\n --> long.jpo:1:7\n  |\n1 | PRINT XYZ\n  |       ^^^",
+    );
+    // a span that starts past the end of the source still gets one caret
+    assert_eq!(
+        diagnostic::render(&undefined(40, 1, 7, 3), "long.jpo", source),
+        UNDEFINED.to_string() + "\n --> long.jpo:1:7\n  |\n1 | PRINT XYZ\n  |       ^",
+    );
+}
+
+#[test]
+fn an_error_at_the_end_of_the_file() {
+    // the end of a file without a trailing newline is one column past its last line
+    let source = "IF TRUE\nPRINT 1";
+    assert_eq!(
+        diagnostic::render(&undefined(source.len(), 2, 8, 0), "eof.jpo", source),
+        UNDEFINED.to_string() + "\n --> eof.jpo:2:8\n  |\n2 | PRINT 1\n  |        ^",
+    );
+    // and with one, the start of a line that isn't there
+    let source = "PRINT 1\n";
+    assert_eq!(
+        diagnostic::render(&undefined(source.len(), 2, 1, 0), "eof.jpo", source),
+        UNDEFINED.to_string() + "\n --> eof.jpo:2:1\n  |\n2 | \n  | ^",
+    );
+}
+
+#[test]
+fn compiled_errors_only_name_the_line() {
+    assert_eq!(diagnostic::render_compiled(&undefined(0, 3, 0, 0), "file.jpoc"), UNDEFINED.to_string() + "\n --> file.jpoc:3");
+    assert_eq!(diagnostic::render_compiled(&undefined(0, 0, 0, 0), "file.jpoc"), UNDEFINED.to_string() + "\n --> file.jpoc");
+}
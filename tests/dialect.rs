@@ -0,0 +1,47 @@
+// scans the same program in every dialect and checks which words become keywords
+// and what the names are called
+use basic_compiler::executer::Interpreter;
+use basic_compiler::scanner::{self, Dialect, TokenType};
+use basic_compiler::parser;
+
+const PROGRAM: &str = "let Total be 1\nrem a comment\nPrint total, TOTAL\n";
+
+fn kinds(dialect: Dialect) -> Vec<(TokenType, String)> {
+    scanner::scan_with(PROGRAM, dialect)
+        .unwrap()
+        .into_iter()
+        .filter(|token| token.tk_type != TokenType::Newline)
+        .map(|token| (token.tk_type, token.lexeme.into_owned()))
+        .collect()
+}
+
+#[test]
+fn uppercase_keywords_only_by_default() {
+    let tokens = kinds(Dialect::default());
+    assert_eq!(tokens[0], (TokenType::Identifier, "let".to_string()));
+    assert!(tokens.iter().any(|token| token.1 == "rem"));
+    assert_eq!(scanner::scan(PROGRAM).unwrap().len(), scanner::scan_with(PROGRAM, Dialect::Uppercase).unwrap().len());
+}
+
+#[test]
+fn folded_names_are_one_variable() {
+    let tokens = kinds(Dialect::FoldCase);
+    assert_eq!(tokens[0].0, TokenType::Initialize);
+    assert_eq!(tokens[1], (TokenType::Identifier, "TOTAL".to_string()));
+    assert!(!tokens.iter().any(|token| token.1.eq_ignore_ascii_case("rem")));
+    assert_eq!(tokens[4], (TokenType::Output, "Print".to_string()));
+
+    let program = parser::parse(&scanner::scan_with(PROGRAM, Dialect::FoldCase).unwrap()).unwrap();
+    assert!(Interpreter::new().execute(&program).is_ok());
+}
+
+#[test]
+fn kept_names_are_case_sensitive() {
+    let tokens = kinds(Dialect::KeepCase);
+    assert_eq!(tokens[0].0, TokenType::Initialize);
+    assert_eq!(tokens[1], (TokenType::Identifier, "Total".to_string()));
+
+    // total and TOTAL were never given a value, only Total was
+    let program = parser::parse(&scanner::scan_with(PROGRAM, Dialect::KeepCase).unwrap()).unwrap();
+    assert!(Interpreter::new().execute(&program).is_err());
+}
@@ -0,0 +1,219 @@
+// throws malformed programs at every stage and checks that each one comes back
+// as a result instead of a panic
+use basic_compiler::executer::Interpreter;
+use basic_compiler::scanner;
+use basic_compiler::vm::Vm;
+use basic_compiler::{compiler, parser};
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+
+const CASES: usize = 5000;
+// loop passes a single run may make
+const PASSES: usize = 100;
+// the command line runs programs on a large stack as well, a test thread's is too
+// small for blocks and expressions nested parser::MAX_NESTING deep
+const STACK: usize = 64 * 1024 * 1024;
+
+// pieces random programs are glued together from
+const WORDS: &[&str] = &[
+    "LET", "BE", "PRINT", "IF", "ELIF", "ELSE", "END", "FOR", "IN", "TO", "STEP", "WHILE",
+    "BREAK", "CONTINUE", "FUNCTION", "RETURN", "GLOBAL", "AND", "OR", "NOT", "MOD", "EQUALS", "NOTEQUALS",
+    "TRUE", "FALSE", "+", "-", "*", "/", "%", "^", "&", "<", "<=", ">", ">=", "=", "<>", "(", ")", ",", ";",
+    "X", "Y", "F", "0", "1", "2", "3", "0.5", "1E-3", "0xFF", "&HFF", "0b10", "1_000", "1.2.3", "9223372036854775807", "2.0", "\"HI\"", "\"", "\"A\"\"B\"", "\"\\n\\u{41}\"", "\\", "\n", "\n", "\n",
+    "REM", "'", "/*", "*/",
+];
+
+const PROGRAMS: &[&str] = &[
+    "LET X BE 4 ^ 1\nPRINT X\n",
+    "LET X BE -2\nPRINT X*-3, 1E-2+X, X<>2, X>=-2\n",
+    "IF X < 5\nPRINT \"SMALL NUM\"\nELIF X < 10\nPRINT \"MEDIUM\"\nELSE\nPRINT \"LARGE NUM\"\nEND\n",
+    "FOR I IN 0 TO 5 STEP 2\nPRINT I\nEND\n",
+    "LET N BE 0\nWHILE N < 3\nN BE N + 1\nEND\n",
+    "LET N BE 0\nWHILE TRUE\nN BE N + 1\nIF N < 3\nCONTINUE\nELIF N > 5\nBREAK\nEND\nPRINT N\nEND\n",
+    "FUNCTION F(A, B)\nRETURN A * B\nEND\nLET X BE F(2, 3)\nPRINT X\n",
+    "FUNCTION G()\nGLOBAL Y\nLET Y BE 1\nEND\nIF TRUE\nLET X BE 2\nEND\n",
+];
+
+// the interpreter is deterministic, so a fixed xorshift keeps failures reproducible
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+fn random_words(random: &mut Random) -> String {
+    let count = random.below(30);
+    let mut program = String::new();
+    for _ in 0..count {
+        program.push_str(WORDS[random.below(WORDS.len())]);
+        if random.below(4) != 0 {
+            program.push(' ');
+        }
+    }
+    program
+}
+
+fn random_bytes(random: &mut Random) -> String {
+    let count = random.below(40);
+    let bytes: Vec<u8> = (0..count).map(|_| random.below(256) as u8).collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+// removes, repeats or cuts off part of a working program
+fn mutate(random: &mut Random) -> String {
+    let program = PROGRAMS[random.below(PROGRAMS.len())];
+    let chars: Vec<char> = program.chars().collect();
+    let start = random.below(chars.len());
+    let end = start + random.below(chars.len() - start + 1);
+    match random.below(3) {
+        0 => chars[..start].iter().chain(&chars[end..]).collect(),
+        1 => chars[..end].iter().chain(&chars[start..]).collect(),
+        _ => chars[..start].iter().collect(),
+    }
+}
+
+fn run(program: &str) {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let Ok(tokens) = scanner::scan(program) else {
+            return;
+        };
+        let program = parser::parse(&tokens);
+        let expr = parser::parse_expression(&tokens);
+        // a WHILE whose condition never changes, or a FOR up to a huge bound, is
+        // stopped after a few passes instead of hanging the test
+        let mut interpreter = Interpreter::new();
+        interpreter.set_max_depth(8);
+        interpreter.set_max_passes(PASSES);
+        if let Ok(program) = program {
+            let _ = interpreter.execute(&program);
+            if let Ok(program) = compiler::compile(&program) {
+                let mut vm = Vm::new();
+                vm.set_max_depth(8);
+                vm.set_max_passes(PASSES);
+                let _ = vm.run(&program);
+            }
+        }
+        if let Ok(expr) = expr {
+            let _ = interpreter.evaluate(&expr);
+        }
+    }));
+    assert!(result.is_ok(), "panicked on {:?}", program);
+}
+
+#[test]
+fn malformed_expressions() {
+    for program in [
+        "LET X BE 1 +", "LET X BE (", "LET X BE )", "LET X BE 1 +\n", "LET X BE (\n",
+        "LET X BE * 2\n", "LET X BE NOT\n", "LET X BE 1 2\n", "LET X BE\n", "LET X BE",
+        "LET X", "LET", "PRINT", "X", "X BE", "LET X BE 1\nX BE\n", "LET X BE \"A\" + 1\n",
+        "IF\nEND\n", "IF 1 +\nEND\n", "WHILE (\nEND\n", "FOR I IN 1 TO 2 STEP\nEND\n",
+        "FUNCTION\nEND\n", "FUNCTION F(\nEND\n", "RETURN\n", "F(\n", "F()\n",
+    ] {
+        run(program);
+    }
+}
+
+#[test]
+fn missing_trailing_newline() {
+    for program in PROGRAMS {
+        run(program.trim_end());
+    }
+}
+
+#[test]
+fn random_word_soup() {
+    let mut random = Random(0x2545_f491_4f6c_dd1d);
+    for _ in 0..CASES {
+        run(&random_words(&mut random));
+    }
+}
+
+#[test]
+fn random_bytes_and_unicode() {
+    let mut random = Random(0x9e37_79b9_7f4a_7c15);
+    for _ in 0..CASES {
+        run(&random_bytes(&mut random));
+    }
+    run("PRINT \"héllo wörld\"\n");
+    run("LET É BE 1\nPRINT É\n");
+}
+
+#[test]
+fn mutated_programs() {
+    let mut random = Random(0xd1b5_4a32_d192_ed03);
+    for _ in 0..CASES {
+        run(&mutate(&mut random));
+    }
+}
+
+#[test]
+fn endless_loops_are_stopped() {
+    for source in ["WHILE TRUE\nEND\n", "LET X BE 0\nWHILE X < 1\nCONTINUE\nEND\n", "FOR I IN 1 TO 9223372036854775807\nEND\n"] {
+        let program = parser::parse(&scanner::scan(source).unwrap()).unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_max_passes(PASSES);
+        let walked = interpreter.execute(&program).unwrap_err();
+        let mut vm = Vm::new();
+        vm.set_max_passes(PASSES);
+        let ran = vm.run(&compiler::compile(&program).unwrap()).unwrap_err();
+        assert_eq!((walked.code(), ran.code()), ("E0311", "E0311"), "{:?}", source);
+    }
+}
+
+#[test]
+fn deeply_nested_expressions() {
+    on_large_stack(nested_expressions);
+}
+
+fn nested_expressions() {
+    let limit = parser::MAX_NESTING;
+    let parens = |depth: usize| std::format!("PRINT {}1{}\n", "(".repeat(depth), ")".repeat(depth));
+    let chain = |terms: usize| std::format!("PRINT 1{}\n", "+1".repeat(terms - 1));
+    let negations = |depth: usize| std::format!("PRINT {}1\n", "-".repeat(depth));
+    let calls = |depth: usize| std::format!("FUNCTION F(N)\nRETURN N\nEND\nPRINT {}1{}\n", "F(".repeat(depth), ")".repeat(depth));
+    let deepest = [parens(limit), chain(limit + 1), negations(limit), calls(limit), std::format!("PRINT ({})\n", chain(limit).trim_start_matches("PRINT ").trim_end())];
+    for program in deepest {
+        run(&program);
+        assert!(parser::parse(&scanner::scan(&program).unwrap()).is_ok(), "{:?}", program);
+    }
+    let too_deep = [parens(limit + 1), parens(3000), chain(limit + 2), chain(5000), chain(100_000), negations(limit + 1), calls(limit + 1)];
+    for program in too_deep {
+        run(&program);
+        let error = parser::parse(&scanner::scan(&program).unwrap()).unwrap_err();
+        assert_eq!(error.code(), "E0214", "{:?}", program);
+        assert!(parser::parse_expression(&scanner::scan(program.trim_start_matches("PRINT ")).unwrap()).is_err());
+    }
+}
+
+#[test]
+fn deeply_nested_blocks() {
+    on_large_stack(nested_blocks);
+}
+
+fn nested_blocks() {
+    let limit = parser::MAX_NESTING;
+    let ifs = |depth: usize| std::format!("{}PRINT 1\n{}", "IF TRUE\n".repeat(depth), "END\n".repeat(depth));
+    let loops = |depth: usize| std::format!("{}BREAK\n{}", "FOR I IN 1 TO 2\nWHILE TRUE\n".repeat(depth / 2), "END\nEND\n".repeat(depth / 2));
+    for program in [ifs(limit), loops(limit), std::format!("{}PRINT (1)\n{}", "IF TRUE\n".repeat(limit - 1), "END\n".repeat(limit - 1))] {
+        run(&program);
+        assert!(parser::parse(&scanner::scan(&program).unwrap()).is_ok(), "{:?}", program);
+    }
+    for program in [ifs(limit + 1), ifs(5000), loops(limit + 2), std::format!("{}PRINT ((1))\n{}", "IF TRUE\n".repeat(limit - 1), "END\n".repeat(limit - 1))] {
+        run(&program);
+        let error = parser::parse(&scanner::scan(&program).unwrap()).unwrap_err();
+        assert_eq!(error.code(), "E0214", "{:?}", program);
+    }
+}
+
+fn on_large_stack(test: fn()) {
+    thread::Builder::new().stack_size(STACK).spawn(test).unwrap().join().unwrap();
+}
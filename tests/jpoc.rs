@@ -0,0 +1,136 @@
+// writes every corpus program to the .jpoc format and reads it back, and checks that
+// damaged files are rejected with an error instead of a panic
+use basic_compiler::bytecode::{Op, Program};
+use basic_compiler::error::{Error, LoadError, RuntimeError};
+use basic_compiler::vm::Vm;
+use basic_compiler::{compiler, jpoc, parser, scanner};
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::process::Command;
+
+const CORPUS: &str = "tests/corpus";
+
+fn corpus() -> Vec<(PathBuf, Program)> {
+    let mut programs = Vec::new();
+    for entry in fs::read_dir(CORPUS).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|extension| extension == "jpo") {
+            let source = fs::read_to_string(&path).unwrap();
+            let compiled = scanner::scan(&source).and_then(|tokens| compiler::compile(&parser::parse(&tokens)?));
+            if let Ok(program) = compiled {
+                programs.push((path, program));
+            }
+        }
+    }
+    programs.sort_by(|a, b| a.0.cmp(&b.0));
+    programs
+}
+
+// a decoded program only keeps the line of every instruction
+fn same_code(decoded: &Program, program: &Program) -> bool {
+    decoded.constants == program.constants
+        && decoded.names == program.names
+        && decoded.functions == program.functions
+        && decoded.locals == program.locals
+        && decoded.code == program.code
+}
+
+fn decode(bytes: &[u8]) -> Result<Program, Error> {
+    match panic::catch_unwind(AssertUnwindSafe(|| jpoc::decode(bytes))) {
+        Ok(result) => result,
+        Err(_) => panic!("decoding panicked on {:?}", bytes),
+    }
+}
+
+#[test]
+fn round_trip() {
+    for (path, program) in corpus() {
+        for lines in [true, false] {
+            let decoded = decode(&jpoc::encode(&program, lines)).unwrap();
+            assert!(same_code(&decoded, &program), "{} changed", path.display());
+            for (decoded, origin) in decoded.origins.iter().zip(&program.origins) {
+                let line = if lines { origin.at.line } else { 0 };
+                assert_eq!(decoded.at.line, line, "line table of {}", path.display());
+            }
+        }
+    }
+}
+
+#[test]
+fn compiled_files_run_like_their_source() {
+    let binary = env!("CARGO_BIN_EXE_basic-compiler");
+    let out = std::env::temp_dir().join(std::format!("jpoc-test-{}", std::process::id()));
+    fs::create_dir_all(&out).unwrap();
+    for (path, _) in corpus() {
+        let compiled = out.join(path.file_name().unwrap()).with_extension("jpoc");
+        let status = Command::new(binary).arg("compile").arg(&path).arg("-o").arg(&compiled).status().unwrap();
+        assert!(status.success(), "could not compile {}", path.display());
+
+        let source = Command::new(binary).arg("run").arg(&path).output().unwrap();
+        let loaded = Command::new(binary).arg("run").arg(&compiled).output().unwrap();
+        assert_eq!(source.stdout, loaded.stdout, "output of {}", path.display());
+        assert_eq!(source.status.code(), loaded.status.code(), "exit code of {}", path.display());
+    }
+    let _ = fs::remove_dir_all(&out);
+}
+
+#[test]
+fn bad_headers() {
+    let bytes = jpoc::encode(&corpus()[0].1, true);
+    assert_eq!(decode(b""), Err(LoadError::NotCompiled.into()));
+    assert_eq!(decode(b"PRINT \"HI\"\n"), Err(LoadError::NotCompiled.into()));
+
+    let mut newer = bytes.clone();
+    newer[4] = 5;
+    assert_eq!(decode(&newer), Err(LoadError::UnsupportedVersion { found: 5, supported: jpoc::VERSION }.into()));
+
+    let mut longer = bytes.clone();
+    longer.push(0);
+    assert!(matches!(decode(&longer), Err(Error::Load(LoadError::Corrupt { .. }))));
+}
+
+#[test]
+fn every_truncation_is_rejected() {
+    for (_, program) in corpus() {
+        let bytes = jpoc::encode(&program, true);
+        for len in 0..bytes.len() {
+            let error = decode(&bytes[..len]).unwrap_err();
+            assert!(matches!(error, Error::Load(_)), "{:?}", error);
+        }
+    }
+}
+
+// flipped bytes may still make a valid program, but decoding must never panic and
+// whatever it accepts has to pass the same checks as a compiled program
+#[test]
+fn flipped_bytes_never_panic() {
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    for (_, program) in corpus() {
+        let bytes = jpoc::encode(&program, true);
+        for _ in 0..500 {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let mut damaged = bytes.clone();
+            let idx = (seed % damaged.len() as u64) as usize;
+            damaged[idx] ^= 1 << ((seed >> 32) % 8);
+            if let Ok(decoded) = decode(&damaged) {
+                let again = decode(&jpoc::encode(&decoded, true)).unwrap();
+                assert_eq!(again, decoded);
+            }
+        }
+    }
+}
+
+#[test]
+fn unset_local_slots_are_undefined() {
+    let source = "LET X BE 1\nIF TRUE\nLET X BE 2\nPRINT X\nEND\n";
+    let mut program = compiler::compile(&parser::parse(&scanner::scan(source).unwrap()).unwrap()).unwrap();
+    // a hand-made file that writes the global instead of the local slot it reads
+    let define = program.code.iter().position(|op| matches!(op, Op::DefineLocal(_))).unwrap();
+    program.code[define] = Op::Define(program.locals[0]);
+    let decoded = decode(&jpoc::encode(&program, true)).unwrap();
+    let error = Vm::new().run(&decoded).unwrap_err();
+    assert!(matches!(error, Error::Runtime(RuntimeError::UndefinedSymbol { ref name, .. }) if name == "X"), "{:?}", error);
+}
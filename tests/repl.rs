@@ -0,0 +1,73 @@
+// feeds lines to the REPL through stdin and checks what it echoes, when it waits for
+// the rest of a block and that a failed input leaves the session as it was
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// what the REPL prints to stdout and to stderr for these lines, without the greeting
+fn repl(input: &str) -> (String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_basic-compiler"))
+        .arg("repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("could not start the REPL");
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stdout = stdout.strip_prefix("Type :help for a list of commands.\n").unwrap_or(&stdout);
+    (stdout.to_string(), String::from_utf8_lossy(&output.stderr).into_owned())
+}
+
+// the error codes in what the REPL printed to stderr, in order
+fn codes(stderr: &str) -> Vec<&str> {
+    stderr.lines().filter_map(|line| line.strip_prefix("error[")?.split(']').next()).collect()
+}
+
+#[test]
+fn expressions_are_echoed_and_statements_are_not() {
+    let (stdout, stderr) = repl("LET X BE 4\nX\nX BE X + 1\nX * 2\nPRINT X\n\"A\" & 1\n(X)\n-X\n");
+    assert_eq!(stdout, "4\n10\n5\nA1\n5\n-5\n");
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn a_call_is_echoed_only_when_it_returns_a_value() {
+    let (stdout, _) = repl("FUNCTION TWICE(N)\nRETURN N * 2\nEND\nFUNCTION SHOW(N)\nPRINT \"SHOW \"; N\nEND\nTWICE(3)\nSHOW(3)\n");
+    assert_eq!(stdout, "6\nSHOW 3\n");
+}
+
+#[test]
+fn blocks_wait_for_their_end() {
+    let (stdout, _) = repl("FOR I IN 1 TO 2\nIF I > 1\nPRINT \"LAST\"\nEND\nPRINT I\nEND\nWHILE FALSE\nEND\nPRINT \"DONE\"\n");
+    assert_eq!(stdout, "1\nLAST\n2\nDONE\n");
+}
+
+#[test]
+fn a_block_comment_waits_for_its_end() {
+    let (stdout, _) = repl("/* a comment\nover two lines */ PRINT \"AFTER\"\n");
+    assert_eq!(stdout, "AFTER\n");
+}
+
+#[test]
+fn an_unclosed_string_does_not_wait() {
+    let (stdout, stderr) = repl("PRINT \"OPEN\nPRINT \"NEXT\"\n");
+    assert_eq!(stdout, "NEXT\n");
+    assert_eq!(codes(&stderr), ["E0102"]);
+}
+
+#[test]
+fn a_failed_input_leaves_no_trace() {
+    let (stdout, stderr) = repl("LET Y BE Z\nPRINT Y\nY\nLET Y BE 1\nY\n");
+    assert_eq!(codes(&stderr), ["E0601", "E0601", "E0601"]);
+    assert_eq!(stdout, "1\n");
+}
+
+#[test]
+fn meta_commands() {
+    let (stdout, stderr) = repl("LET X BE 1\nLET NAME BE \"N\"\n:vars\n:reset\n:vars\nX\n:nothing\n:quit\nPRINT \"NEVER\"\n");
+    assert_eq!(stdout, "NAME = N\nX = 1\n");
+    assert_eq!(codes(&stderr), ["E0601"]);
+    assert!(stderr.contains("UNKNOWN COMMAND ':nothing'"));
+}
@@ -0,0 +1,89 @@
+// scans generated programs of a few megabytes and checks that scanning time grows
+// linearly with the size of the input. the timing tests depend on the machine, so
+// they only run with --ignored (add --nocapture to see the throughput)
+use basic_compiler::scanner::{self, TokenType};
+use std::time::{Duration, Instant};
+
+// one block of a generated script, with non-ASCII text in strings and comments
+const BLOCK: &str = "REM généré automatiquement\n\
+LET TOTAL_é BE 0 ' café\n\
+FOR I IN 1 TO 10 STEP 2\n\
+TOTAL_é BE TOTAL_é+I*2-(I%3)^2\n\
+IF TOTAL_é<>4 AND I>=1\n\
+PRINT \"naïve \\\"ÆØÅ\\\" \u{1F600} \"\"→\"\" \\u{263A}\"; TOTAL_é\n\
+END\n\
+END /* ∑ of everything */\n";
+
+// a script of lines, or a single line as long as all of them without the line
+// comments that would hide the rest of it
+fn script(blocks: usize, one_line: bool) -> String {
+    if !one_line {
+        return BLOCK.repeat(blocks);
+    }
+    let line: Vec<&str> = BLOCK
+        .lines()
+        .filter(|line| !line.starts_with("REM"))
+        .map(|line| line.split(" '").next().unwrap())
+        .collect();
+    (line.join(" ") + " ").repeat(blocks)
+}
+
+// the fastest of a few runs, so one slow run doesn't decide the result
+fn time(input: &str) -> (Duration, usize) {
+    let mut best = Duration::MAX;
+    let mut tokens = 0;
+    for _ in 0..3 {
+        let started = Instant::now();
+        let scanned = scanner::scan(input).unwrap();
+        best = best.min(started.elapsed());
+        tokens = scanned.len();
+    }
+    (best, tokens)
+}
+
+fn grows_linearly(one_line: bool) {
+    let small = script(5_000, one_line);
+    let large = script(20_000, one_line);
+    let (small_time, small_tokens) = time(&small);
+    let (large_time, large_tokens) = time(&large);
+    assert_eq!(large_tokens, small_tokens * 4);
+    println!(
+        "{:.1} MB in {:?} ({:.1} MB/s), {:.1} MB in {:?}",
+        small.len() as f64 / 1e6, small_time, small.len() as f64 / 1e6 / small_time.as_secs_f64(),
+        large.len() as f64 / 1e6, large_time,
+    );
+    // four times the input takes about four times as long, a quadratic scanner
+    // would take sixteen times as long
+    assert!(large_time < small_time * 10, "{:?} against {:?}", large_time, small_time);
+}
+
+#[test]
+#[ignore]
+fn scanning_is_linear() {
+    grows_linearly(false);
+}
+
+#[test]
+#[ignore]
+fn scanning_a_long_line_is_linear() {
+    grows_linearly(true);
+}
+
+#[test]
+fn every_block_gives_the_same_tokens() {
+    for one_line in [false, true] {
+        let tokens = scanner::scan(&script(1, one_line)).unwrap().len();
+        assert_eq!(tokens, if one_line { 41 } else { 49 });
+        assert_eq!(scanner::scan(&script(1_000, one_line)).unwrap().len(), tokens * 1_000);
+    }
+}
+
+#[test]
+fn spans_count_characters() {
+    let input = script(2, false);
+    let tokens = scanner::scan(&input).unwrap();
+    let last = tokens.iter().rev().find(|token| token.tk_type == TokenType::Literal && token.lexeme.starts_with('"')).unwrap();
+    assert_eq!((last.span.line, last.span.col), (14, 7));
+    assert!(last.lexeme.ends_with("\\u{263A}\""));
+    assert_eq!(scanner::unescape(&last.lexeme), "naïve \"ÆØÅ\" \u{1F600} \"→\" \u{263A}");
+}